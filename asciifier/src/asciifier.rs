@@ -1,35 +1,70 @@
-use std::{fs::File, io::BufReader, ops::Deref, path::PathBuf};
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Read, Seek, Write},
+    ops::Deref,
+    path::PathBuf,
+};
 
 use ab_glyph::FontArc;
 use image::{
-    GenericImage, GrayImage, ImageBuffer, ImageFormat, ImageReader, Luma, Pixel, Rgb, RgbImage,
+    imageops::FilterType, DynamicImage, GenericImage, GenericImageView, GrayImage, ImageBuffer,
+    ImageDecoder, ImageFormat, ImageReader, Luma, Pixel, Rgb, RgbImage, Rgba, RgbaImage, SubImage,
 };
-use palette::Srgb;
-use rgb::FromSlice;
+use palette::{IntoColor, Lab, Srgb};
+use rgb::{FromSlice, RGB8};
 
 use crate::{
+    cell_grid::{Cell, CellGrid},
     chars::{
-        font_handler::{CharAlignment, CharDistributionType, CharacterBackground},
+        font_handler::{
+            CharAlignment, CharDistributionMatch, CharDistributionType, CharacterBackground,
+            TwoColorMatch,
+        },
         Chars,
     },
-    error::{AsciiError, IntoAsciiError, IntoConvertNotCalledResult},
-    grouped_image::GroupedImage,
+    color_palette::ColorPalette,
+    dither::{DiffusionBuffer, ErrorDiffusion},
+    error::{AsciiError, GroupedImageError, IntoAsciiError, IntoConvertNotCalledResult},
+    grouped_image::{GroupedImage, PixelGroup},
+    layout::{self, AlignmentSearch, EdgePolicy, GridSize, Transform},
+    preprocess::Filter,
+    render::{
+        ansi::{self, AnsiOptions},
+        html::{self, HtmlOptions},
+        svg::{self, SvgOptions},
+        OutputFormat,
+    },
+    style::{Canvas, GlyphColor, OutputStyle},
+    subcell::{block_match, braille_char, braille_pattern, BlockMatch, CellMode},
 };
 
 const DEFAULT_FONT: &[u8] = include_bytes!("../../assets/fonts/Hasklug-2.otf");
 //const DEFAULT_CHARS: &str =
 //    "^°<>|{}≠¿'][¢¶`.,:;-_#'+*?=)(/&%$§qwertzuiopasdfghjklyxcvbnmQWERTZUIOPASDFGHJKLYXCVBNM∇∕∑∏∇∆∃∫∬∮≋⊋⊂⊃⊞⊟⊠⊪⊩∸∷∶∶∵∴∾⊢⊯⊮⊭⊬⊫⊪⊩⊨⊧⊦⊥⊤⊣⊡";
 const DEFAULT_CHARS: &str = "∇∕∑∏∇∆∃∫∬∮≋⊋⊂⊃⊪⊩∸∷∶∶∵∴∾⊢⊯⊮⊭⊬⊫⊪⊩⊨⊧⊦⊥⊤⊣";
+/// Cells with a lower average alpha than this are left empty.
+const DEFAULT_TRANSPARENCY_THRESHOLD: f64 = 0.5;
+/// The width divided by the height of the cells of most terminal fonts.
+pub const TERMINAL_CELL_ASPECT: f64 = 0.5;
 
 pub struct Asciifier {
-    image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    image: RgbaImage,
+}
+
+/// Decodes the image and turns it upright as its EXIF orientation says, like
+/// phone cameras write it.
+fn decode<R: BufRead + Seek>(reader: ImageReader<R>) -> Result<DynamicImage, AsciiError> {
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
 }
 
 impl Asciifier {
     pub fn load_image(path: impl Into<PathBuf>) -> Result<Self, AsciiError> {
-        Ok(Self {
-            image: ImageReader::open(path.into())?.decode()?.into(),
-        })
+        Ok(Self::from_image(decode(ImageReader::open(path.into())?)?))
     }
 
     pub fn load_image_with_format(
@@ -39,9 +74,36 @@ impl Asciifier {
         let reader = BufReader::new(File::open(path.into()).ascii_err()?);
         let mut buffer = ImageReader::with_format(reader, format);
         buffer.set_format(format);
-        Ok(Self {
-            image: buffer.decode()?.into(),
-        })
+        Ok(Self::from_image(decode(buffer)?))
+    }
+
+    /// Decodes an image held in memory, guessing its format from the content.
+    pub fn load_image_from_memory(bytes: &[u8]) -> Result<Self, AsciiError> {
+        Self::load_image_from_reader(Cursor::new(bytes))
+    }
+
+    pub fn load_image_from_memory_with_format(
+        bytes: &[u8],
+        format: ImageFormat,
+    ) -> Result<Self, AsciiError> {
+        Ok(Self::from_image(decode(ImageReader::with_format(
+            Cursor::new(bytes),
+            format,
+        ))?))
+    }
+
+    /// Decodes an image from any reader, guessing its format from the content.
+    pub fn load_image_from_reader(reader: impl Read + Seek) -> Result<Self, AsciiError> {
+        let reader = ImageReader::new(BufReader::new(reader))
+            .with_guessed_format()
+            .map_err(image::ImageError::IoError)?;
+        Ok(Self::from_image(decode(reader)?))
+    }
+
+    pub fn from_image(image: impl Into<DynamicImage>) -> Self {
+        Self {
+            image: image.into().into_rgba8(),
+        }
     }
 
     pub fn font(
@@ -53,11 +115,45 @@ impl Asciifier {
     }
 }
 
+impl From<DynamicImage> for Asciifier {
+    fn from(value: DynamicImage) -> Self {
+        Self::from_image(value)
+    }
+}
+
+impl From<RgbImage> for Asciifier {
+    fn from(value: RgbImage) -> Self {
+        Self::from_image(value)
+    }
+}
+
+impl From<RgbaImage> for Asciifier {
+    fn from(value: RgbaImage) -> Self {
+        Self::from_image(value)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ImageBuilder {
     chars: Chars,
-    image: ImageBuffer<Rgb<u8>, Vec<u8>>,
-    asciified_image: Option<ImageBuffer<Rgb<u8>, Vec<u8>>>,
+    image: RgbaImage,
+    transparency_threshold: f64,
+    transparent_background: bool,
+    style: OutputStyle,
+    cell_mode: CellMode,
+    palette: Option<ColorPalette>,
+    strict_palette: bool,
+    error_diffusion: Option<ErrorDiffusion>,
+    luminance_model: LuminanceModel,
+    transforms: Vec<Transform>,
+    filters: Vec<Filter>,
+    grid_size: GridSize,
+    resampling: FilterType,
+    edge_policy: EdgePolicy,
+    cell_aspect: Option<f64>,
+    alignment_search: Option<AlignmentSearch>,
+    asciified_image: Option<DynamicImage>,
+    cell_grid: Option<CellGrid>,
 }
 
 impl ImageBuilder {
@@ -71,15 +167,265 @@ impl ImageBuilder {
         self
     }
 
-    pub fn convert(&mut self) -> Result<&mut Self, AsciiError> {
+    /// Sets the average alpha, between `0` and `1`, below which a cell is treated as
+    /// empty and gets no glyph.
+    pub fn transparency_threshold(&mut self, threshold: f64) -> &mut Self {
+        self.transparency_threshold = threshold;
+        self
+    }
+
+    /// Makes [`ImageBuilder::convert`] produce an rgba image in which everything
+    /// but the glyphs is transparent, instead of an rgb image on the canvas of the
+    /// [`OutputStyle`].
+    pub fn transparent_background(&mut self, transparent: bool) -> &mut Self {
+        self.transparent_background = transparent;
+        self
+    }
+
+    /// Sets the canvas and glyph colors of the converted image. The canvas is ignored
+    /// if [`ImageBuilder::transparent_background`] is set, [`Canvas::FontBackground`]
+    /// takes the color of the [`CharacterBackground`] of the font.
+    pub fn output_style(&mut self, style: OutputStyle) -> &mut Self {
+        self.style = style;
+        self
+    }
+
+    /// Sets whether the cells are filled with the characters of the font or with
+    /// sub-cell patterns like Braille.
+    pub fn cell_mode(&mut self, mode: CellMode) -> &mut Self {
+        self.cell_mode = mode;
+        self
+    }
+
+    /// Restricts every color of the output to the palette, `None` allows all
+    /// colors again. The colors are restricted before the glyphs are drawn, so their
+    /// anti-aliased edges still blend between the palette colors unless
+    /// [`ImageBuilder::strict_palette`] is set.
+    pub fn palette(&mut self, palette: Option<ColorPalette>) -> &mut Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Also maps the blended edges of the glyphs to the palette, so the output holds
+    /// no other color at all. On a transparent background the edges are made fully
+    /// opaque or transparent instead. Off by default, since the hard edges look
+    /// rougher.
+    pub fn strict_palette(&mut self, strict: bool) -> &mut Self {
+        self.strict_palette = strict;
+        self
+    }
+
+    /// Pushes the error of every cell on to its neighbours, `None` matches every
+    /// cell on its own.
+    pub fn error_diffusion(&mut self, diffusion: Option<ErrorDiffusion>) -> &mut Self {
+        self.error_diffusion = diffusion;
+        self
+    }
+
+    /// Sets how the brightness of the pixels is computed for matching the chars.
+    pub fn luminance_model(&mut self, model: LuminanceModel) -> &mut Self {
+        self.luminance_model = model;
+        self
+    }
+
+    /// Appends a transform to the ones applied to the image, in order, before it is
+    /// filtered.
+    pub fn add_transform(&mut self, transform: Transform) -> &mut Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    pub fn set_transforms(&mut self, transforms: Vec<Transform>) -> &mut Self {
+        self.transforms = transforms;
+        self
+    }
+
+    /// Appends a filter to the ones applied to the image, in order, before it is
+    /// converted.
+    pub fn add_filter(&mut self, filter: Filter) -> &mut Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn set_filters(&mut self, filters: Vec<Filter>) -> &mut Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Sets how many cells the grid has, the image is resampled to fit it exactly.
+    pub fn grid_size(&mut self, size: GridSize) -> &mut Self {
+        self.grid_size = size;
+        self
+    }
+
+    /// Sets the filter used when resampling the image for the [`GridSize`].
+    pub fn resampling(&mut self, filter: FilterType) -> &mut Self {
+        self.resampling = filter;
+        self
+    }
+
+    /// Sets what happens to the edges of the image that do not fill a whole cell.
+    pub fn edge_policy(&mut self, policy: EdgePolicy) -> &mut Self {
+        self.edge_policy = policy;
+        self
+    }
+
+    /// Sets the width divided by the height of the cells the output is shown in.
+    /// The image is sampled so that it keeps its proportions in those cells, which
+    /// squashes the rasterized output. `None`, the default, samples the image in
+    /// the box of the rasterized font, and [`ImageBuilder::to_text`] and
+    /// [`ImageBuilder::to_ansi`] then repeat or drop rows to show it in cells of
+    /// [`TERMINAL_CELL_ASPECT`]. Setting an aspect samples the image for it directly,
+    /// which keeps more detail, and every output uses the grid as it is.
+    pub fn cell_aspect(&mut self, aspect: Option<f64>) -> &mut Self {
+        self.cell_aspect = aspect;
+        self
+    }
+
+    /// Searches for the offset and scale of the grid whose cells match best, `None`
+    /// starts the grid at the top left pixel.
+    pub fn alignment_search(&mut self, search: Option<AlignmentSearch>) -> &mut Self {
+        self.alignment_search = search;
+        self
+    }
+
+    /// The candidate of the search whose cells have the lowest average distance to
+    /// their match. The average is compared rather than the total, since the
+    /// candidates differ in their number of cells. Candidates left without a whole
+    /// cell are skipped, failing only if none is left.
+    fn best_alignment(
+        &self,
+        image: RgbaImage,
+        search: &AlignmentSearch,
+    ) -> Result<RgbaImage, AsciiError> {
+        let char_box = self.chars.char_box();
+        let mut best = None;
+        let mut error = None;
+        for factor in search.scales(self.grid_size) {
+            let scaled = if *factor == 1. {
+                image.clone()
+            } else {
+                layout::scale(&image, *factor, self.resampling)
+            };
+            for offset in search.offsets(char_box) {
+                let shifted = self.edge_policy.shift(&scaled, offset, char_box);
+                let candidate = match self.edge_policy.apply(shifted, char_box, self.resampling) {
+                    Ok(candidate) => candidate,
+                    Err(candidate_error) => {
+                        // the first one describes the image before it was shifted
+                        error.get_or_insert(candidate_error);
+                        continue;
+                    }
+                };
+                let distance = self.average_distance(&candidate)?;
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_distance, _)| distance < *best_distance)
+                {
+                    best = Some((distance, candidate));
+                }
+            }
+        }
+        match (best, error) {
+            (Some((_, candidate)), _) => Ok(candidate),
+            (None, Some(error)) => Err(error),
+            (None, None) => Ok(image),
+        }
+    }
+
+    /// The average distance of the cells that are not transparent to their best
+    /// match, ignoring the error diffusion.
+    fn average_distance(&self, image: &RgbaImage) -> Result<f64, AsciiError> {
         let (font_width, font_height) = self.chars.char_box();
+        let grouped_image = GroupedImage::new(
+            font_width,
+            font_height,
+            image.clone(),
+            self.chars.background(),
+            self.style.whitening,
+            self.luminance_model,
+        )?;
+        let (mut total, mut count) = (0., 0);
+        for (row_i, column) in grouped_image.groups.iter().enumerate() {
+            for (col_i, group) in column.iter().enumerate() {
+                if group.alpha < self.transparency_threshold {
+                    continue;
+                }
+                let view = image.view(
+                    (font_width * row_i) as u32,
+                    (font_height * col_i) as u32,
+                    font_width as u32,
+                    font_height as u32,
+                );
+                total += match self.cell_mode {
+                    CellMode::Font => self.chars.best_match(&group.coverage).distance,
+                    CellMode::TwoColorFont => self.chars.best_two_color_match(view).distance,
+                    CellMode::Braille(dot_fill) => {
+                        braille_pattern(&group.coverage, dot_fill, (row_i, col_i)).1
+                    }
+                    CellMode::Blocks(kind) => block_match(view, kind).distance,
+                };
+                count += 1;
+            }
+        }
+        Ok(if count == 0 {
+            f64::INFINITY
+        } else {
+            total / count as f64
+        })
+    }
 
-        //let image = self.convert_to_gray();
+    pub fn convert(&mut self) -> Result<&mut Self, AsciiError> {
+        let (font_width, font_height) = self.chars.char_box();
 
-        let grouped_image = GroupedImage::new(font_width, font_height, self.image.clone())?;
+        let image = self
+            .transforms
+            .iter()
+            .fold(self.image.clone(), |image, transform| {
+                transform.apply(&image)
+            });
+        let image = self
+            .filters
+            .iter()
+            .fold(image, |image, filter| filter.apply(&image));
+        // shown in cells of a different shape, the image has to be stretched by the
+        // inverse to keep its proportions
+        let vertical_scale = self
+            .cell_aspect
+            .map_or(1., |aspect| aspect * font_height as f64 / font_width as f64);
+        let image = self.grid_size.resize(
+            image,
+            (font_width, font_height),
+            vertical_scale,
+            self.resampling,
+        )?;
+        let image = match &self.alignment_search {
+            Some(search) => self.best_alignment(image, search)?,
+            None => image,
+        };
+        let image = self
+            .edge_policy
+            .apply(image, (font_width, font_height), self.resampling)?;
 
         let (adjusted_width, adjusted_height) =
-            get_adjusted_size(&self.image, &(font_width, font_height));
+            get_adjusted_size(&image, &(font_width, font_height));
+        // a crop or rotation can leave less than a single cell of the image
+        if adjusted_width == 0 || adjusted_height == 0 {
+            return Err(AsciiError::GroupedImage(GroupedImageError::TooSmall {
+                width: image.width(),
+                height: image.height(),
+                char_box: (font_width, font_height),
+            }));
+        }
+
+        let grouped_image = GroupedImage::new(
+            font_width,
+            font_height,
+            image.clone(),
+            self.chars.background(),
+            self.style.whitening,
+            self.luminance_model,
+        )?;
 
         assert_eq!(
             adjusted_width as f64 / font_width as f64,
@@ -90,42 +436,222 @@ impl ImageBuilder {
             grouped_image.num_cols().unwrap() as f64
         );
 
-        let mut final_image = RgbImage::new(adjusted_width as u32, adjusted_height as u32);
-
-        for (row_i, group_row) in grouped_image.groups.iter().enumerate() {
-            for (col_i, group) in group_row.iter().enumerate() {
-                let rasterized_char = self.chars.best_match(&group.coverage);
-                let Rgb([r, g, b]) = group.color;
-                let color = Srgb::new(r as f64 / 255f64, g as f64 / 255f64, b as f64 / 255f64)
-                    .into_linear();
+        let mut final_image = if self.transparent_background {
+            RgbaImage::new(adjusted_width as u32, adjusted_height as u32)
+        } else {
+            self.style.canvas.image(
+                &image,
+                adjusted_width as u32,
+                adjusted_height as u32,
+                self.chars.background(),
+            )
+        };
+        if let (false, Some(palette)) = (self.transparent_background, &self.palette) {
+            palette.apply(&mut final_image);
+        }
+        let restrict = |color| match &self.palette {
+            Some(palette) => palette.nearest(color),
+            None => color,
+        };
+        let masks = self.cell_mode.masks((font_width, font_height));
+        let (num_columns, num_rows) = (
+            grouped_image.num_rows(),
+            grouped_image.num_cols().unwrap_or(0),
+        );
+        let mut cell_grid = CellGrid::new(
+            num_columns,
+            num_rows,
+            (font_width, font_height),
+            self.cell_mode,
+        );
+        let mut diffusion = self
+            .error_diffusion
+            .map(|diffusion| DiffusionBuffer::new(diffusion, num_columns, num_rows));
+        // tones outside of what the cells can show would leave an error behind that
+        // only grows, so they are not diffused
+        let (min_ink, max_ink) = match self.cell_mode {
+            CellMode::Braille(_) => (0., 1.),
+            _ => self.chars.ink_range(),
+        };
+
+        // the cells are processed in scan order, so that the error of a cell can be
+        // pushed to the cells after it
+        for col_i in 0..num_rows {
+            for row_i in 0..num_columns {
+                let group = &grouped_image.groups[row_i][col_i];
+                let diffused;
+                let group = match &diffusion {
+                    Some(buffer) => {
+                        diffused = diffuse(group, buffer, (row_i, col_i));
+                        &diffused
+                    }
+                    None => group,
+                };
+                let background = restrict(
+                    self.style
+                        .canvas
+                        .cell_background(group, self.chars.background()),
+                );
+                let glyph_color = restrict(self.style.glyph_color.cell_color(group));
 
                 let start_glyph_x = (font_width * row_i) as u32;
                 let start_glyph_y = (font_height * col_i) as u32;
 
-                let mut sub_image = final_image.sub_image(
+                let cell_view = image.view(
                     start_glyph_x,
                     start_glyph_y,
                     font_width as u32,
                     font_height as u32,
                 );
-                rasterized_char.raster_letter.enumerate_pixels().for_each(
-                    |(x, y, Luma([luma]))| {
-                        let color = color * (*luma as f64 / 255.);
-                        sub_image.put_pixel(
-                            x,
-                            y,
-                            [
-                                (color.red * 255.) as u8,
-                                (color.green * 255.) as u8,
-                                (color.blue * 255.) as u8,
-                            ]
-                            .into(),
-                        );
-                    },
+                let mut sub_image = final_image.sub_image(
+                    start_glyph_x,
+                    start_glyph_y,
+                    font_width as u32,
+                    font_height as u32,
                 );
+                if let (false, Canvas::CellColorDimmed(_)) =
+                    (self.transparent_background, self.style.canvas)
+                {
+                    fill(&mut sub_image, background);
+                }
+
+                if group.alpha < self.transparency_threshold {
+                    cell_grid.rows[col_i].push(Cell {
+                        character: ' ',
+                        color: glyph_color,
+                        background,
+                        coverage: group.coverage.clone(),
+                        distance: 0.,
+                    });
+                    continue;
+                }
+
+                let (character, distance, mask, glyph_color, background, ink) = match self.cell_mode
+                {
+                    CellMode::Font => {
+                        let CharDistributionMatch {
+                            distance,
+                            rasterized_char,
+                        } = self.chars.best_match(&group.coverage);
+                        (
+                            rasterized_char.character,
+                            distance,
+                            &rasterized_char.raster_letter,
+                            glyph_color,
+                            background,
+                            Some(rasterized_char.adjusted_coverage.avg()),
+                        )
+                    }
+                    CellMode::TwoColorFont => {
+                        let TwoColorMatch {
+                            distance,
+                            rasterized_char,
+                            foreground,
+                            background,
+                        } = self.chars.best_two_color_match(cell_view);
+                        let (foreground, background) = (restrict(foreground), restrict(background));
+                        if !self.transparent_background {
+                            fill(&mut sub_image, background);
+                        }
+                        (
+                            rasterized_char.character,
+                            distance,
+                            &rasterized_char.raster_letter,
+                            foreground,
+                            background,
+                            None,
+                        )
+                    }
+                    CellMode::Braille(dot_fill) => {
+                        let (pattern, distance) =
+                            braille_pattern(&group.coverage, dot_fill, (row_i, col_i));
+                        (
+                            braille_char(pattern),
+                            distance,
+                            &masks[pattern as usize],
+                            glyph_color,
+                            background,
+                            Some(pattern.count_ones() as f64 / 8.),
+                        )
+                    }
+                    CellMode::Blocks(kind) => {
+                        let BlockMatch {
+                            pattern,
+                            foreground,
+                            background,
+                            distance,
+                        } = block_match(cell_view, kind);
+                        let (foreground, background) = (restrict(foreground), restrict(background));
+                        if !self.transparent_background {
+                            fill(&mut sub_image, background);
+                        }
+                        (
+                            kind.character(pattern),
+                            distance,
+                            &masks[pattern as usize],
+                            foreground,
+                            background,
+                            None,
+                        )
+                    }
+                };
+                if let (Some(buffer), Some(ink)) = (&mut diffusion, ink) {
+                    let color_error = match (buffer.diffuse_color(), &self.style.glyph_color) {
+                        (true, GlyphColor::Cell) => {
+                            [0, 1, 2].map(|i| group.color[i] as f64 - glyph_color[i] as f64)
+                        }
+                        _ => [0.; 3],
+                    };
+                    let target = group.coverage.avg().clamp(min_ink, max_ink);
+                    buffer.push(row_i, col_i, target - ink, color_error);
+                }
+                cell_grid.rows[col_i].push(Cell {
+                    character,
+                    color: glyph_color,
+                    background,
+                    coverage: group.coverage.clone(),
+                    distance,
+                });
+                let Rgb([r, g, b]) = glyph_color;
+                let color = Srgb::new(r, g, b).into_linear::<f64>();
+
+                mask.enumerate_pixels().for_each(|(x, y, Luma([luma]))| {
+                    let coverage = *luma as f64 / 255.;
+                    let pixel = if self.transparent_background {
+                        // on a transparent canvas the glyph is blended through its
+                        // alpha instead of over the canvas
+                        Rgba([r, g, b, *luma])
+                    } else {
+                        // the glyph is blended over the canvas in linear light and
+                        // only encoded again for the output
+                        let Rgba([canvas_r, canvas_g, canvas_b, _]) = sub_image.get_pixel(x, y);
+                        let canvas = Srgb::new(canvas_r, canvas_g, canvas_b).into_linear::<f64>();
+                        let blended: Srgb<u8> =
+                            Srgb::from_linear(canvas * (1. - coverage) + color * coverage);
+                        Rgba([blended.red, blended.green, blended.blue, 255])
+                    };
+                    sub_image.put_pixel(x, y, pixel);
+                });
+            }
+        }
+        if let (true, Some(palette)) = (self.strict_palette, &self.palette) {
+            if self.transparent_background {
+                // the glyph colors are from the palette already, only their alpha
+                // would blend them with what is behind the image
+                final_image.pixels_mut().for_each(|Rgba([.., alpha])| {
+                    *alpha = if *alpha >= 128 { 255 } else { 0 };
+                });
+            } else {
+                palette.apply(&mut final_image);
             }
         }
-        self.asciified_image = Some(final_image);
+        let final_image = DynamicImage::ImageRgba8(final_image);
+        self.asciified_image = Some(if self.transparent_background {
+            final_image
+        } else {
+            DynamicImage::ImageRgb8(final_image.into_rgb8())
+        });
+        self.cell_grid = Some(cell_grid);
         Ok(self)
     }
 
@@ -138,9 +664,106 @@ impl ImageBuilder {
         Ok(self)
     }
 
-    pub fn get_image(&self) -> Option<&ImageBuffer<Rgb<u8>, Vec<u8>>> {
+    /// Writes the converted image in the given format, without the format having to
+    /// be inferred from a file extension like with [`ImageBuilder::save_to`].
+    pub fn write_to(
+        &mut self,
+        mut writer: impl Write,
+        format: OutputFormat,
+    ) -> Result<&mut Self, AsciiError> {
+        let bytes = match format {
+            OutputFormat::Image(image_format) => {
+                // most image encoders need to seek, which an arbitrary writer can't
+                let mut buffer = Cursor::new(vec![]);
+                self.asciified_image
+                    .ok_or_ascii_err()?
+                    .write_to(&mut buffer, image_format)
+                    .ascii_err()?;
+                buffer.into_inner()
+            }
+            OutputFormat::Text => self.to_text()?.into_bytes(),
+            OutputFormat::Ansi(options) => self.to_ansi(&options)?.into_bytes(),
+            OutputFormat::Html(options) => self.to_html(&options)?.into_bytes(),
+            OutputFormat::Svg(options) => self.to_svg(&options)?.into_bytes(),
+            OutputFormat::Json => self.to_json()?.into_bytes(),
+        };
+        writer.write_all(&bytes).ascii_err()?;
+        writer.flush().ascii_err()?;
+
+        Ok(self)
+    }
+
+    /// Returns the converted image, an rgba image if
+    /// [`ImageBuilder::transparent_background`] was set and an rgb image otherwise.
+    pub fn get_image(&self) -> Option<&DynamicImage> {
         self.asciified_image.as_ref()
     }
+
+    pub fn get_cell_grid(&self) -> Option<&CellGrid> {
+        self.cell_grid.as_ref()
+    }
+
+    /// Returns the characters chosen by [`ImageBuilder::convert`], one `Vec` per
+    /// line of text from the top of the image to the bottom.
+    pub fn text_grid(&self) -> Result<Vec<Vec<char>>, AsciiError> {
+        Ok(self.cell_grid.ok_or_ascii_err()?.text_grid())
+    }
+
+    /// Returns the characters chosen by [`ImageBuilder::convert`] as a single
+    /// string with one line per row, keeping the proportions in a terminal as
+    /// described for [`ImageBuilder::cell_aspect`].
+    pub fn to_text(&self) -> Result<String, AsciiError> {
+        Ok(self.terminal_grid()?.to_text())
+    }
+
+    /// Returns the converted image as text colored with ANSI escape sequences,
+    /// ready to be printed to a terminal supporting the selected color mode. The
+    /// proportions are kept as for [`ImageBuilder::to_text`].
+    pub fn to_ansi(&self, options: &AnsiOptions) -> Result<String, AsciiError> {
+        Ok(ansi::to_ansi(&*self.terminal_grid()?, options))
+    }
+
+    /// The grid resampled to the rows of a terminal, unless the image was already
+    /// sampled for the cell aspect it is shown in.
+    fn terminal_grid(&self) -> Result<Cow<'_, CellGrid>, AsciiError> {
+        let grid = self.cell_grid.ok_or_ascii_err()?;
+        if self.cell_aspect.is_some() {
+            return Ok(Cow::Borrowed(grid));
+        }
+        let (char_width, char_height) = grid.char_box();
+        Ok(Cow::Owned(grid.resample_rows(
+            TERMINAL_CELL_ASPECT * char_height as f64 / char_width as f64,
+        )))
+    }
+
+    /// Returns the converted image as a standalone html document, with the font
+    /// used for the conversion embedded if requested.
+    pub fn to_html(&self, options: &HtmlOptions) -> Result<String, AsciiError> {
+        Ok(html::to_html(
+            self.cell_grid.ok_or_ascii_err()?,
+            self.chars.font(),
+            self.chars.font_height(),
+            options,
+        ))
+    }
+
+    /// Returns the converted cell grid, including the match metadata of every cell,
+    /// as json that can be loaded again with [`CellGrid::from_json`].
+    pub fn to_json(&self) -> Result<String, AsciiError> {
+        self.cell_grid.ok_or_ascii_err()?.to_json()
+    }
+
+    /// Returns the converted image as an svg document which, unlike the raster
+    /// image, can be scaled to any size without losing detail.
+    pub fn to_svg(&self, options: &SvgOptions) -> Result<String, AsciiError> {
+        Ok(svg::to_svg(
+            self.cell_grid.ok_or_ascii_err()?,
+            self.chars.font(),
+            self.chars.font_height(),
+            self.chars.alignment(),
+            options,
+        ))
+    }
 }
 
 pub fn get_adjusted_size<P, Container>(
@@ -224,10 +847,7 @@ impl FontBuilder {
         self
     }
 
-    pub fn build(
-        &mut self,
-        image: ImageBuffer<Rgb<u8>, Vec<u8>>,
-    ) -> Result<ImageBuilder, AsciiError> {
+    pub fn build(&mut self, image: RgbaImage) -> Result<ImageBuilder, AsciiError> {
         let FontBuilder {
             chars,
             font,
@@ -247,16 +867,111 @@ impl FontBuilder {
         Ok(ImageBuilder {
             chars,
             image,
+            transparency_threshold: DEFAULT_TRANSPARENCY_THRESHOLD,
+            transparent_background: false,
+            style: OutputStyle::default(),
+            cell_mode: CellMode::default(),
+            palette: None,
+            strict_palette: false,
+            error_diffusion: None,
+            luminance_model: LuminanceModel::default(),
+            transforms: vec![],
+            filters: vec![],
+            grid_size: GridSize::Native,
+            resampling: FilterType::CatmullRom,
+            edge_policy: EdgePolicy::default(),
+            cell_aspect: None,
+            alignment_search: None,
             asciified_image: None,
+            cell_grid: None,
         })
     }
 }
 
+/// The group with the errors pushed on to its cell added to its coverage and color.
+fn diffuse(group: &PixelGroup, buffer: &DiffusionBuffer, (x, y): (usize, usize)) -> PixelGroup {
+    let coverage_error = buffer.coverage_error(x, y);
+    let color_error = buffer.color_error(x, y);
+    let Rgb(color) = group.color;
+    PixelGroup {
+        color: Rgb([0, 1, 2].map(|i| (color[i] as f64 + color_error[i]).clamp(0., 255.) as u8)),
+        coverage: group
+            .coverage
+            .from_func(|coverage| coverage + coverage_error),
+        ..group.clone()
+    }
+}
+
+fn fill(sub_image: &mut SubImage<&mut RgbaImage>, Rgb([r, g, b]): Rgb<u8>) {
+    let (width, height) = sub_image.dimensions();
+    for y in 0..height {
+        for x in 0..width {
+            sub_image.put_pixel(x, y, Rgba([r, g, b, 255]));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChannel {
+    Red,
+    Green,
+    Blue,
+}
+
+/// How the brightness of a pixel is computed from its color, which decides the
+/// coverage the chars are matched against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LuminanceModel {
+    /// The luma of SD video, a good fit for most photos.
+    #[default]
+    Rec601,
+    /// The luma of HD video and sRGB, which weights green more and blue less.
+    Rec709,
+    /// The lightness of CIELAB, which follows how bright colors are perceived most
+    /// closely, at the cost of being the slowest.
+    CieLabLightness,
+    /// The HSP color model, a root of the squared channels that keeps saturated
+    /// colors brighter than the luma models.
+    Hsp,
+    /// Only a single channel, for scans or images whose content is in one channel.
+    Channel(ColorChannel),
+    /// The brightest channel, which treats every saturated color as bright.
+    MaxChannel,
+    /// The darkest channel, which treats only colors close to white as bright.
+    MinChannel,
+}
+
+impl LuminanceModel {
+    fn luminance(&self, RGB8 { r, g, b }: RGB8) -> u8 {
+        let (r_f, g_f, b_f) = (r as f32, g as f32, b as f32);
+        match self {
+            Self::Rec601 => (0.299 * r_f + 0.587 * g_f + 0.114 * b_f) as u8,
+            Self::Rec709 => (0.2126 * r_f + 0.7152 * g_f + 0.0722 * b_f) as u8,
+            Self::CieLabLightness => {
+                let lab: Lab = Srgb::new(r, g, b).into_format::<f32>().into_color();
+                (lab.l * 2.55).clamp(0., 255.) as u8
+            }
+            Self::Hsp => (0.299 * r_f * r_f + 0.587 * g_f * g_f + 0.114 * b_f * b_f)
+                .sqrt()
+                .min(255.) as u8,
+            Self::Channel(ColorChannel::Red) => r,
+            Self::Channel(ColorChannel::Green) => g,
+            Self::Channel(ColorChannel::Blue) => b,
+            Self::MaxChannel => r.max(g).max(b),
+            Self::MinChannel => r.min(g).min(b),
+        }
+    }
+}
+
 pub fn convert_to_gray(image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> ImageBuffer<Luma<u8>, Vec<u8>> {
-    let pixels = image
-        .as_rgb()
-        .iter()
-        .map(|p| (0.299 * p.r as f32 + 0.587 * p.g as f32 + 0.114 * p.b as f32) as u8)
-        .collect();
+    convert_to_gray_with(image, LuminanceModel::Rec601)
+}
+
+/// Like [`convert_to_gray`], with the brightness computed by the model.
+pub fn convert_to_gray_with(
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    model: LuminanceModel,
+) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let pixels = image.as_rgb().iter().map(|p| model.luminance(*p)).collect();
     GrayImage::from_raw(image.width(), image.height(), pixels).unwrap()
 }
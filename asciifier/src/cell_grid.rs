@@ -0,0 +1,223 @@
+use std::io::Read;
+
+use image::Rgb;
+use serde::{de::Error, Deserialize, Serialize};
+
+use crate::{
+    error::{AsciiError, IntoAsciiError},
+    subcell::CellMode,
+    Coverage,
+};
+
+/// A single cell of the converted image, one per pixel group of the
+/// [`GroupedImage`](crate::grouped_image::GroupedImage).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cell {
+    pub character: char,
+    /// The color the character is drawn with.
+    #[serde(with = "rgb_serde")]
+    pub color: Rgb<u8>,
+    /// The color of the canvas behind the character.
+    #[serde(with = "rgb_serde")]
+    pub background: Rgb<u8>,
+    /// The coverage of the pixel group the character was matched against.
+    pub coverage: Coverage,
+    /// How far the chosen character is from the cell, lower is a better match. What
+    /// is measured depends on the [`CellGrid::cell_mode`]: the distance between the
+    /// coverage of the cell and the coverage of the character or Braille pattern
+    /// for [`CellMode::Font`] and [`CellMode::Braille`], the root mean square error
+    /// of the reconstructed pixels in linear light for [`CellMode::TwoColorFont`]
+    /// and [`CellMode::Blocks`]. Distances of different modes are not comparable.
+    pub distance: f64,
+}
+
+/// The result of a conversion as a grid of cells.
+///
+/// Unlike the [`GroupedImage`](crate::grouped_image::GroupedImage), which stores its
+/// groups column by column, the cells here are stored row by row from the top of
+/// the image to the bottom, so that each row is one line of text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CellGrid {
+    num_columns: usize,
+    num_rows: usize,
+    char_box: (usize, usize),
+    /// Missing in grids written before it was recorded, which were all made with
+    /// [`CellMode::Font`].
+    #[serde(default)]
+    cell_mode: CellMode,
+    pub(crate) rows: Vec<Vec<Cell>>,
+}
+
+impl CellGrid {
+    pub(crate) fn new(
+        num_columns: usize,
+        num_rows: usize,
+        char_box: (usize, usize),
+        cell_mode: CellMode,
+    ) -> Self {
+        Self {
+            num_columns,
+            num_rows,
+            char_box,
+            cell_mode,
+            rows: vec![Vec::with_capacity(num_columns); num_rows],
+        }
+    }
+
+    /// Loads a grid previously written with [`CellGrid::to_json`].
+    pub fn from_json(json: &str) -> Result<Self, AsciiError> {
+        serde_json::from_str::<Self>(json).ascii_err()?.validated()
+    }
+
+    pub fn from_json_reader(reader: impl Read) -> Result<Self, AsciiError> {
+        serde_json::from_reader::<_, Self>(reader)
+            .ascii_err()?
+            .validated()
+    }
+
+    pub fn to_json(&self) -> Result<String, AsciiError> {
+        serde_json::to_string(self).ascii_err()
+    }
+
+    pub fn to_json_pretty(&self) -> Result<String, AsciiError> {
+        serde_json::to_string_pretty(self).ascii_err()
+    }
+
+    /// Makes sure the stored dimensions match the cells, since they can not be
+    /// trusted after loading a grid from json.
+    fn validated(self) -> Result<Self, AsciiError> {
+        if self.rows.len() != self.num_rows
+            || self.rows.iter().any(|row| row.len() != self.num_columns)
+        {
+            return Err(serde_json::Error::custom(format!(
+                "the cells do not match the grid dimensions of {} columns and {} rows",
+                self.num_columns, self.num_rows
+            )))
+            .ascii_err();
+        }
+        Ok(self)
+    }
+
+    pub fn rows(&self) -> &[Vec<Cell>] {
+        &self.rows
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.num_columns
+    }
+
+    /// The size in pixels every cell takes up in the rasterized image.
+    pub fn char_box(&self) -> (usize, usize) {
+        self.char_box
+    }
+
+    /// The mode the cells were filled with, which decides what their distance
+    /// measures.
+    pub fn cell_mode(&self) -> CellMode {
+        self.cell_mode
+    }
+
+    /// Stretches the grid vertically by the factor by repeating or dropping whole
+    /// rows, for showing it in cells of a different shape than it was matched in.
+    pub(crate) fn resample_rows(&self, factor: f64) -> CellGrid {
+        if self.rows.is_empty() {
+            return self.clone();
+        }
+        let num_rows = ((self.num_rows as f64 * factor).round() as usize).max(1);
+        let rows = (0..num_rows)
+            .map(|row| {
+                // the source row under the center of the new one
+                let source = ((row as f64 + 0.5) / factor) as usize;
+                self.rows[source.min(self.num_rows - 1)].clone()
+            })
+            .collect();
+        CellGrid {
+            num_columns: self.num_columns,
+            num_rows,
+            char_box: self.char_box,
+            cell_mode: self.cell_mode,
+            rows,
+        }
+    }
+
+    pub fn text_grid(&self) -> Vec<Vec<char>> {
+        self.rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.character).collect())
+            .collect()
+    }
+
+    /// Returns the chosen characters as one string, with each row of the grid
+    /// terminated by a newline.
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity((self.num_columns() + 1) * self.num_rows());
+        for row in &self.rows {
+            text.extend(row.iter().map(|cell| cell.character));
+            text.push('\n');
+        }
+        text
+    }
+}
+
+pub(crate) mod rgb_serde {
+    use image::Rgb;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        Rgb(color): &Rgb<u8>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        color.serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Rgb<u8>, D::Error> {
+        <[u8; 3]>::deserialize(deserializer).map(Rgb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subcell::BlockKind;
+
+    fn grid() -> CellGrid {
+        let mut grid = CellGrid::new(2, 1, (8, 16), CellMode::Blocks(BlockKind::Quadrants));
+        for (character, value) in [('▘', 0.25), ('█', 1.)] {
+            grid.rows[0].push(Cell {
+                character,
+                color: Rgb([10, 20, 30]),
+                background: Rgb([0, 0, 0]),
+                coverage: Coverage {
+                    squares: [value; 16],
+                },
+                distance: value / 2.,
+            });
+        }
+        grid
+    }
+
+    #[test]
+    fn json_round_trip_keeps_the_grid() {
+        let grid = grid();
+        let loaded = CellGrid::from_json(&grid.to_json().unwrap()).unwrap();
+        assert_eq!(loaded, grid);
+    }
+
+    #[test]
+    fn json_with_mismatched_dimensions_is_rejected() {
+        let json = grid()
+            .to_json()
+            .unwrap()
+            .replace("\"num_columns\":2", "\"num_columns\":3");
+        assert!(matches!(
+            CellGrid::from_json(&json),
+            Err(AsciiError::Json(_))
+        ));
+    }
+}
@@ -3,7 +3,11 @@ pub mod font_handler;
 
 use ab_glyph::FontArc;
 use char::{RasterizedChar, RasterizedCharBuilder};
-use font_handler::{CharAlignment, CharDistributionType, CharacterBackground};
+use font_handler::{
+    CharAlignment, CharDistributionMatch, CharDistributionType, CharacterBackground, TwoColorMatch,
+};
+
+use image::{RgbaImage, SubImage};
 
 use crate::{error::AsciiError, Coverage};
 
@@ -29,7 +33,7 @@ impl Chars {
         background: CharacterBackground,
     ) -> Result<Self, AsciiError> {
         let (mut rasterized_chars, char_box) =
-            Self::rasterize_chars(&chars, &font, font_height, alignment, background)?;
+            Self::rasterize_chars(&chars, &font, font_height, alignment)?;
 
         distribution.adjust_coverage(&mut rasterized_chars);
 
@@ -57,23 +61,38 @@ impl Chars {
             .adjust_coverage(&mut self.rasterized_chars);
     }
 
-    pub(crate) fn best_match(&self, target_coverage: &Coverage) -> &RasterizedChar {
+    pub(crate) fn best_match(&self, target_coverage: &Coverage) -> CharDistributionMatch<'_> {
         self.rasterized_chars
             .iter()
             .map(|char| char.match_coverage(target_coverage))
             .min_by(|match_a, match_b| match_a.partial_cmp(match_b).unwrap())
             .unwrap()
-            .rasterized_char
+    }
+
+    /// Chooses the glyph together with a foreground and background color that
+    /// reconstruct the pixels of the cell best.
+    pub(crate) fn best_two_color_match(&self, pixels: SubImage<&RgbaImage>) -> TwoColorMatch<'_> {
+        self.rasterized_chars
+            .iter()
+            .map(|char| char.fit_colors(&pixels))
+            .min_by(|match_a, match_b| match_a.distance.total_cmp(&match_b.distance))
+            .unwrap()
+    }
+
+    /// The lowest and highest average adjusted coverage of the chars, which is the
+    /// range of tones they are able to reproduce.
+    pub(crate) fn ink_range(&self) -> (f64, f64) {
+        self.rasterized_chars
+            .iter()
+            .map(|char| char.adjusted_coverage.avg())
+            .fold((f64::MAX, f64::MIN), |(min, max), avg| {
+                (min.min(avg), max.max(avg))
+            })
     }
 
     fn re_rasterize(&mut self) -> Result<(), AsciiError> {
-        let (rasterized_chars, char_box) = Self::rasterize_chars(
-            &self.chars,
-            &self.font,
-            self.font_height,
-            self.alignment,
-            self.background,
-        )?;
+        let (rasterized_chars, char_box) =
+            Self::rasterize_chars(&self.chars, &self.font, self.font_height, self.alignment)?;
         self.rasterized_chars = rasterized_chars;
         self.char_box = char_box;
         self.distribution
@@ -89,21 +108,19 @@ impl Chars {
     /// * `chars` - The list of chars to rasterize
     /// * `font` - The font to use for the rasterization
     /// * `(font_width, font_height)` - The wanted size of the font, main component
-    ///     here is the height since, but width can also be determined id wanted. Although
-    ///     its only considered if wider then minimum width.
+    ///   here is the height since, but width can also be determined id wanted. Although
+    ///   its only considered if wider then minimum width.
     /// * `alignment` - since not each char is equally wide, this defines the chars
-    ///     placement on the X axis
-    /// * `character_bg` - color of the background TODO: what the hell is this actually
+    ///   placement on the X axis
     fn rasterize_chars(
         chars: &[char],
         font: &FontArc,
         font_height: usize,
         alignment: CharAlignment,
-        character_bg: CharacterBackground,
     ) -> Result<(Vec<RasterizedChar>, (usize, usize)), AsciiError> {
         let builders = chars
             .iter()
-            .map(|c| RasterizedCharBuilder::new(*c, font_height, font, &alignment, &character_bg))
+            .map(|c| RasterizedCharBuilder::new(*c, font_height, font, &alignment))
             .collect::<Vec<_>>();
 
         let font_box =
@@ -121,4 +138,20 @@ impl Chars {
     pub(crate) fn char_box(&self) -> (usize, usize) {
         self.char_box
     }
+
+    pub(crate) fn font(&self) -> &FontArc {
+        &self.font
+    }
+
+    pub(crate) fn font_height(&self) -> usize {
+        self.font_height
+    }
+
+    pub(crate) fn alignment(&self) -> CharAlignment {
+        self.alignment
+    }
+
+    pub(crate) fn background(&self) -> CharacterBackground {
+        self.background
+    }
 }
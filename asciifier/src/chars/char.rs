@@ -1,12 +1,13 @@
 use ab_glyph::{Font, FontArc, Glyph, Point, PxScale};
-use image::{GenericImageView, GrayImage, ImageBuffer, Luma};
+use image::{GenericImageView, GrayImage, ImageBuffer, Luma, Rgb, RgbaImage, SubImage};
+use palette::{LinSrgb, Srgb};
 
 use crate::{
     error::{AsciiError, IntoGlyphOutlineMissingResult},
     Coverage,
 };
 
-use super::font_handler::{CharAlignment, CharDistributionMatch, CharacterBackground};
+use super::font_handler::{CharAlignment, CharDistributionMatch, TwoColorMatch};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct RasterizedChar {
@@ -39,19 +40,92 @@ impl RasterizedChar {
         }
     }
 
-    pub(crate) fn match_coverage(&self, target_coverage: &Coverage) -> CharDistributionMatch {
+    pub(crate) fn match_coverage(&self, target_coverage: &Coverage) -> CharDistributionMatch<'_> {
         CharDistributionMatch {
             distance: self.adjusted_coverage.dist(target_coverage),
             rasterized_char: self,
         }
     }
 
+    /// Finds the foreground and background color for which the glyph reproduces the
+    /// pixels best, by solving the least squares problem of `pixel = background *
+    /// (1 - ink) + foreground * ink` per channel in linear light, weighted by the
+    /// alpha of the pixels.
+    ///
+    /// * `pixels` - the cell, which has to be of the same size as the rasterized letter
+    pub(crate) fn fit_colors(&self, pixels: &SubImage<&RgbaImage>) -> TwoColorMatch<'_> {
+        // sums of the normal equations, `b` for background and `f` for foreground
+        let (mut bb, mut bf, mut ff, mut weight) = (0f64, 0f64, 0f64, 0f64);
+        let (mut pb, mut pf, mut pp) = ([0f64; 3], [0f64; 3], [0f64; 3]);
+        for (Luma([ink]), (_, _, pixel)) in self.raster_letter.pixels().zip(pixels.pixels()) {
+            let [r, g, b, a] = pixel.0;
+            let LinSrgb {
+                red: r,
+                green: g,
+                blue: b,
+                ..
+            } = Srgb::new(r, g, b).into_linear::<f64>();
+            let (ink, w) = (*ink as f64 / 255., a as f64 / 255.);
+            bb += w * (1. - ink) * (1. - ink);
+            bf += w * (1. - ink) * ink;
+            ff += w * ink * ink;
+            weight += w;
+            for (i, p) in [r, g, b].into_iter().enumerate() {
+                pb[i] += w * (1. - ink) * p;
+                pf[i] += w * ink * p;
+                pp[i] += w * p * p;
+            }
+        }
+
+        let determinant = bb * ff - bf * bf;
+        let mut error = 0.;
+        let (mut foreground, mut background) = ([0f64; 3], [0f64; 3]);
+        for i in 0..3 {
+            let (fg, bg) = if determinant.abs() > 1e-9 {
+                (
+                    (bb * pf[i] - bf * pb[i]) / determinant,
+                    (ff * pb[i] - bf * pf[i]) / determinant,
+                )
+            } else {
+                // the glyph is empty or has the same ink everywhere, so both colors
+                // fall back to the mean
+                let mean = if weight > 0. {
+                    (pb[i] + pf[i]) / weight
+                } else {
+                    0.
+                };
+                (mean, mean)
+            };
+            let (fg, bg) = (fg.clamp(0., 1.), bg.clamp(0., 1.));
+            error += pp[i] - 2. * (bg * pb[i] + fg * pf[i])
+                + bb * bg * bg
+                + 2. * bf * bg * fg
+                + ff * fg * fg;
+            foreground[i] = fg;
+            background[i] = bg;
+        }
+        let encode = |[r, g, b]: [f64; 3]| {
+            let color: Srgb<u8> = Srgb::from_linear(LinSrgb::new(r, g, b));
+            Rgb([color.red, color.green, color.blue])
+        };
+
+        TwoColorMatch {
+            distance: if weight > 0. {
+                (error.max(0.) / (weight * 3.)).sqrt()
+            } else {
+                0.
+            },
+            rasterized_char: self,
+            foreground: encode(foreground),
+            background: encode(background),
+        }
+    }
+
     fn rasterize_glyph(
         glyph: &Glyph,
         font: &FontArc,
         (bounding_width, bounding_height): (usize, usize),
         alignment: CharAlignment,
-        character_bg: CharacterBackground,
     ) -> Result<ImageBuffer<Luma<u8>, Vec<u8>>, AsciiError> {
         let q = font.outline_glyph(glyph.clone()).ok_or_ascii_err(glyph)?;
 
@@ -67,10 +141,9 @@ impl RasterizedChar {
 
         let mut letter = GrayImage::new((bounding_width) as u32, (bounding_height) as u32);
         q.draw(|x, y, c| {
-            let cov = (match character_bg {
-                CharacterBackground::Black => c,
-                CharacterBackground::White => 1f32 / c,
-            } * 255f32) as u8;
+            // the letter always holds the ink of the glyph, for a white background the
+            // ink is inverted when matching and painting instead
+            let cov = (c * 255f32) as u8;
             let x = match alignment {
                 CharAlignment::Left => x,
                 CharAlignment::Center => x + ((bounding_width as u32 - char_width as u32) / 2),
@@ -90,7 +163,6 @@ impl RasterizedChar {
         font: &FontArc,
         (bounding_width, bounding_height): (usize, usize),
         alignment: CharAlignment,
-        character_bg: CharacterBackground,
     ) -> Result<Coverage, AsciiError> {
         const INTERNAL_SCALE_FACTOR: usize = 4;
 
@@ -113,10 +185,7 @@ impl RasterizedChar {
 
         let mut letter = GrayImage::new((bounding_width) as u32, (bounding_height) as u32);
         q.draw(|x, y, c| {
-            let cov = (match character_bg {
-                CharacterBackground::Black => c,
-                CharacterBackground::White => 1f32 / c,
-            } * 255f32) as u8;
+            let cov = (c * 255f32) as u8;
             let x = match alignment {
                 CharAlignment::Left => x,
                 CharAlignment::Center => x + ((bounding_width as u32 - char_width as u32) / 2),
@@ -128,12 +197,7 @@ impl RasterizedChar {
             }
         });
 
-        Ok(Coverage::new(letter.view(
-            0,
-            0,
-            letter.width(),
-            letter.height(),
-        ))?)
+        Coverage::new(letter.view(0, 0, letter.width(), letter.height()))
     }
 
     /// Finds the ideal box size for the font and the requested Glyphs
@@ -176,7 +240,6 @@ pub(crate) struct RasterizedCharBuilder<'builder> {
     pub(crate) glyph: Glyph,
     pub(crate) font: &'builder FontArc,
     pub(crate) alignment: &'builder CharAlignment,
-    pub(crate) background: &'builder CharacterBackground,
     pub(crate) glyph_box: Option<(usize, usize)>,
     pub(crate) rasterized_letter: Option<ImageBuffer<Luma<u8>, Vec<u8>>>,
     pub(crate) coverage: Option<Coverage>,
@@ -188,7 +251,6 @@ impl<'builder> RasterizedCharBuilder<'builder> {
         font_height: usize,
         font: &'builder FontArc,
         alignment: &'builder CharAlignment,
-        background: &'builder CharacterBackground,
     ) -> Self {
         let glyph = font
             .glyph_id(char)
@@ -198,7 +260,6 @@ impl<'builder> RasterizedCharBuilder<'builder> {
             glyph,
             font,
             alignment,
-            background,
             glyph_box: None,
             rasterized_letter: None,
             coverage: None,
@@ -210,7 +271,6 @@ impl<'builder> RasterizedCharBuilder<'builder> {
             glyph,
             font,
             alignment,
-            background,
             glyph_box,
             rasterized_letter,
             coverage,
@@ -221,14 +281,12 @@ impl<'builder> RasterizedCharBuilder<'builder> {
             font,
             font_box,
             **alignment,
-            **background,
         )?);
         *coverage = Some(RasterizedChar::get_coverage(
             glyph,
             font,
             font_box,
             **alignment,
-            **background,
         )?);
         *glyph_box = Some(font_box);
         Ok(self)
@@ -1,3 +1,5 @@
+use image::Rgb;
+
 use crate::chars::char::RasterizedChar;
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -8,13 +10,36 @@ pub enum CharAlignment {
     Right,
 }
 
+/// The background the glyphs are drawn on, which decides whether the ink of a glyph
+/// stands for brightness or for darkness.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum CharacterBackground {
+    /// Light glyphs on a dark canvas, dense glyphs are used for bright areas.
     #[default]
     Black,
+    /// Dark glyphs on a light canvas, dense glyphs are used for dark areas.
     White,
 }
 
+impl CharacterBackground {
+    /// The canvas color used when no other canvas was chosen.
+    pub fn canvas_color(&self) -> Rgb<u8> {
+        match self {
+            CharacterBackground::Black => Rgb([0, 0, 0]),
+            CharacterBackground::White => Rgb([255, 255, 255]),
+        }
+    }
+
+    /// Turns the luminance of an image into the amount of ink a glyph needs to
+    /// reproduce it on this background.
+    pub(crate) fn ink(&self, luminance: f64) -> f64 {
+        match self {
+            CharacterBackground::Black => luminance,
+            CharacterBackground::White => 1. - luminance,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct CharDistributionMatch<'a> {
     pub distance: f64,
@@ -27,6 +52,18 @@ impl<'a> PartialOrd for CharDistributionMatch<'a> {
     }
 }
 
+/// A glyph together with the two colors that reconstruct a cell best when the
+/// glyph is drawn in `foreground` on top of `background`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoColorMatch<'a> {
+    /// The root mean square difference between the pixels of the cell and the
+    /// drawn glyph, between `0` and `1`.
+    pub distance: f64,
+    pub rasterized_char: &'a RasterizedChar,
+    pub foreground: Rgb<u8>,
+    pub background: Rgb<u8>,
+}
+
 #[derive(Debug, Clone, Default, Copy, PartialEq, Eq)]
 pub enum CharDistributionType {
     //Even,
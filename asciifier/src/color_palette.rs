@@ -0,0 +1,222 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use image::{Rgb, Rgba, RgbaImage};
+use palette::{color_difference::Ciede2000, Lab};
+
+use crate::{
+    error::{AsciiError, PaletteError},
+    render::ansi::to_lab,
+};
+
+const GAME_BOY: [[u8; 3]; 4] = [[15, 56, 15], [48, 98, 48], [139, 172, 15], [155, 188, 15]];
+
+const CGA: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x00, 0x00, 0xaa],
+    [0x00, 0xaa, 0x00],
+    [0x00, 0xaa, 0xaa],
+    [0xaa, 0x00, 0x00],
+    [0xaa, 0x00, 0xaa],
+    [0xaa, 0x55, 0x00],
+    [0xaa, 0xaa, 0xaa],
+    [0x55, 0x55, 0x55],
+    [0x55, 0x55, 0xff],
+    [0x55, 0xff, 0x55],
+    [0x55, 0xff, 0xff],
+    [0xff, 0x55, 0x55],
+    [0xff, 0x55, 0xff],
+    [0xff, 0xff, 0x55],
+    [0xff, 0xff, 0xff],
+];
+
+/// The C64 colors as measured by Pepto.
+const C64: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0xff, 0xff, 0xff],
+    [0x68, 0x37, 0x2b],
+    [0x70, 0xa4, 0xb2],
+    [0x6f, 0x3d, 0x86],
+    [0x58, 0x8d, 0x43],
+    [0x35, 0x28, 0x79],
+    [0xb8, 0xc7, 0x6f],
+    [0x6f, 0x4f, 0x25],
+    [0x43, 0x39, 0x00],
+    [0x9a, 0x67, 0x59],
+    [0x44, 0x44, 0x44],
+    [0x6c, 0x6c, 0x6c],
+    [0x9a, 0xd2, 0x84],
+    [0x6c, 0x5e, 0xb5],
+    [0x95, 0x95, 0x95],
+];
+
+/// A restricted set of colors every color of the output is mapped to, using the
+/// closest entry by CIEDE2000 in CIELAB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorPalette {
+    colors: Vec<Rgb<u8>>,
+    lab: Vec<Lab>,
+}
+
+impl ColorPalette {
+    pub fn new(colors: Vec<Rgb<u8>>) -> Result<Self, AsciiError> {
+        if colors.is_empty() {
+            return Err(AsciiError::Palette(PaletteError::Empty));
+        }
+        let lab = colors.iter().map(|Rgb(color)| to_lab(*color)).collect();
+        Ok(Self { colors, lab })
+    }
+
+    /// The four greens of the original Game Boy.
+    pub fn game_boy() -> Self {
+        Self::from_array(&GAME_BOY)
+    }
+
+    /// The 16 colors of the CGA text modes.
+    pub fn cga() -> Self {
+        Self::from_array(&CGA)
+    }
+
+    /// The 16 colors of the Commodore 64.
+    pub fn c64() -> Self {
+        Self::from_array(&C64)
+    }
+
+    fn from_array(colors: &[[u8; 3]]) -> Self {
+        // the built in palettes are never empty
+        Self::new(colors.iter().copied().map(Rgb).collect()).unwrap()
+    }
+
+    /// Loads a GIMP `.gpl` palette, or a list of hex colors for any other file.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, AsciiError> {
+        let path = path.into();
+        let content = fs::read_to_string(&path)?;
+        let is_gpl = path.extension().is_some_and(|extension| extension == "gpl")
+            || content.starts_with("GIMP Palette");
+        if is_gpl {
+            Self::from_gpl(&content)
+        } else {
+            Self::from_hex_list(&content)
+        }
+    }
+
+    /// Parses a GIMP palette, whose colors are lines of three decimal channels
+    /// optionally followed by a name.
+    pub fn from_gpl(content: &str) -> Result<Self, AsciiError> {
+        let mut lines = content.lines().enumerate();
+        match lines.next() {
+            Some((_, header)) if header.trim() == "GIMP Palette" => {}
+            _ => return Err(AsciiError::Palette(PaletteError::MissingGplHeader)),
+        }
+
+        let mut colors = vec![];
+        for (index, line) in lines {
+            let line = line.trim();
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("Name:")
+                || line.starts_with("Columns:")
+            {
+                continue;
+            }
+            let channels = line
+                .split_whitespace()
+                .take(3)
+                .map(str::parse::<u8>)
+                .collect::<Result<Vec<_>, _>>();
+            match channels.as_deref() {
+                Ok(&[r, g, b]) => colors.push(Rgb([r, g, b])),
+                _ => return Err(invalid_line(index, line)),
+            }
+        }
+        Self::new(colors)
+    }
+
+    /// Parses one color per line written as `RRGGBB` or `#RRGGBB`, as exported by
+    /// most palette sites.
+    pub fn from_hex_list(content: &str) -> Result<Self, AsciiError> {
+        let mut colors = vec![];
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let hex = line.strip_prefix('#').unwrap_or(line);
+            if hex.len() != 6 || !hex.is_ascii() {
+                return Err(invalid_line(index, line));
+            }
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+            match (channel(0), channel(2), channel(4)) {
+                (Ok(r), Ok(g), Ok(b)) => colors.push(Rgb([r, g, b])),
+                _ => return Err(invalid_line(index, line)),
+            }
+        }
+        Self::new(colors)
+    }
+
+    pub fn colors(&self) -> &[Rgb<u8>] {
+        &self.colors
+    }
+
+    /// The entry of the palette that looks closest to the color.
+    pub fn nearest(&self, Rgb(color): Rgb<u8>) -> Rgb<u8> {
+        let lab = to_lab(color);
+        self.lab
+            .iter()
+            .map(|entry| entry.difference(lab))
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| self.colors[index])
+            .unwrap()
+    }
+
+    /// Maps every pixel of the image to the palette, keeping its alpha.
+    pub(crate) fn apply(&self, image: &mut RgbaImage) {
+        // images tend to repeat their colors, and matching is far from free
+        let mut nearest = HashMap::new();
+        image.pixels_mut().for_each(|Rgba([r, g, b, _])| {
+            let Rgb(color) = *nearest
+                .entry([*r, *g, *b])
+                .or_insert_with(|| self.nearest(Rgb([*r, *g, *b])));
+            [*r, *g, *b] = color;
+        });
+    }
+}
+
+fn invalid_line(index: usize, line: &str) -> AsciiError {
+    AsciiError::Palette(PaletteError::InvalidLine {
+        line: index + 1,
+        content: line.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_error(result: Result<ColorPalette, AsciiError>) -> (usize, String) {
+        match result {
+            Err(AsciiError::Palette(PaletteError::InvalidLine { line, content })) => {
+                (line, content)
+            }
+            other => panic!("expected an invalid line, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn gpl_reports_the_line_error() {
+        let gpl = "GIMP Palette\nName: Test\n# comment\n0 0 0 Black\n\n255 0 Red\n";
+        assert_eq!(
+            line_error(ColorPalette::from_gpl(gpl)),
+            (6, "255 0 Red".into())
+        );
+    }
+
+    #[test]
+    fn hex_list_reports_the_line_error() {
+        let hex = "#000000\n\nffffff\n#12345g\n";
+        assert_eq!(
+            line_error(ColorPalette::from_hex_list(hex)),
+            (4, "#12345g".into())
+        );
+    }
+}
@@ -0,0 +1,116 @@
+/// The weights with which the error of a cell is pushed to the cells after it, as
+/// `(x offset, y offset, weight)`.
+type Kernel = &'static [(isize, usize, f64)];
+
+const FLOYD_STEINBERG: Kernel = &[
+    (1, 0, 7. / 16.),
+    (-1, 1, 3. / 16.),
+    (0, 1, 5. / 16.),
+    (1, 1, 1. / 16.),
+];
+
+/// Only pushes on three quarters of the error, which keeps more contrast.
+const ATKINSON: Kernel = &[
+    (1, 0, 1. / 8.),
+    (2, 0, 1. / 8.),
+    (-1, 1, 1. / 8.),
+    (0, 1, 1. / 8.),
+    (1, 1, 1. / 8.),
+    (0, 2, 1. / 8.),
+];
+
+const JARVIS_JUDICE_NINKE: Kernel = &[
+    (1, 0, 7. / 48.),
+    (2, 0, 5. / 48.),
+    (-2, 1, 3. / 48.),
+    (-1, 1, 5. / 48.),
+    (0, 1, 7. / 48.),
+    (1, 1, 5. / 48.),
+    (2, 1, 3. / 48.),
+    (-2, 2, 1. / 48.),
+    (-1, 2, 3. / 48.),
+    (0, 2, 5. / 48.),
+    (1, 2, 3. / 48.),
+    (2, 2, 1. / 48.),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffusionKernel {
+    #[default]
+    FloydSteinberg,
+    Atkinson,
+    /// Spreads the error over the most cells, giving the smoothest gradients.
+    JarvisJudiceNinke,
+}
+
+impl DiffusionKernel {
+    fn weights(&self) -> Kernel {
+        match self {
+            DiffusionKernel::FloydSteinberg => FLOYD_STEINBERG,
+            DiffusionKernel::Atkinson => ATKINSON,
+            DiffusionKernel::JarvisJudiceNinke => JARVIS_JUDICE_NINKE,
+        }
+    }
+}
+
+/// Pushes the error between the coverage of a cell and the coverage of its chosen
+/// character to the neighbouring cells, so gradients do not band with small
+/// charsets. Only used by the modes matching the coverage, which are
+/// [`CellMode::Font`](crate::subcell::CellMode::Font) and
+/// [`CellMode::Braille`](crate::subcell::CellMode::Braille).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorDiffusion {
+    pub kernel: DiffusionKernel,
+    /// Also pushes on the difference between the color of a cell and the color of
+    /// its glyph, which only exists when the colors are restricted by a
+    /// [`ColorPalette`](crate::color_palette::ColorPalette).
+    pub diffuse_color: bool,
+}
+
+/// The errors collected for every cell of the grid while it is processed in scan
+/// order.
+pub(crate) struct DiffusionBuffer {
+    diffusion: ErrorDiffusion,
+    width: usize,
+    height: usize,
+    coverage: Vec<f64>,
+    color: Vec<[f64; 3]>,
+}
+
+impl DiffusionBuffer {
+    pub(crate) fn new(diffusion: ErrorDiffusion, width: usize, height: usize) -> Self {
+        Self {
+            diffusion,
+            width,
+            height,
+            coverage: vec![0.; width * height],
+            color: vec![[0.; 3]; width * height],
+        }
+    }
+
+    pub(crate) fn diffuse_color(&self) -> bool {
+        self.diffusion.diffuse_color
+    }
+
+    pub(crate) fn coverage_error(&self, x: usize, y: usize) -> f64 {
+        self.coverage[y * self.width + x]
+    }
+
+    pub(crate) fn color_error(&self, x: usize, y: usize) -> [f64; 3] {
+        self.color[y * self.width + x]
+    }
+
+    pub(crate) fn push(&mut self, x: usize, y: usize, coverage: f64, color: [f64; 3]) {
+        for (offset_x, offset_y, weight) in self.diffusion.kernel.weights() {
+            let (Some(x), y) = (x.checked_add_signed(*offset_x), y + offset_y) else {
+                continue;
+            };
+            if x >= self.width || y >= self.height {
+                continue;
+            }
+            let i = y * self.width + x;
+            self.coverage[i] += coverage * weight;
+            (0..3).for_each(|channel| self.color[i][channel] += color[channel] * weight);
+        }
+    }
+}
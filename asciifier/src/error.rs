@@ -1,7 +1,8 @@
 use std::{fmt::Display, io};
 
 use ab_glyph::{Glyph, InvalidFont, OutlinedGlyph};
-use image::{flat, ImageBuffer, Rgb};
+use enterpolation::linear::LinearError;
+use image::flat;
 
 #[derive(Debug)]
 pub enum AsciiError {
@@ -11,6 +12,9 @@ pub enum AsciiError {
     ImageError(ImageError),
     GroupedImage(GroupedImageError),
     ConvertNotCalled,
+    Json(serde_json::Error),
+    Palette(PaletteError),
+    Gradient(LinearError),
     ManyErrors(Vec<AsciiError>),
 }
 
@@ -22,7 +26,23 @@ pub enum FontParseErrors {
 
 #[derive(Debug)]
 pub enum GroupedImageError {
-    RowIndexOutOfBounds { index: usize, row_len: usize },
+    RowIndexOutOfBounds {
+        index: usize,
+        row_len: usize,
+    },
+    /// The image does not fill a single cell, so there is nothing to convert.
+    TooSmall {
+        width: u32,
+        height: u32,
+        char_box: (usize, usize),
+    },
+}
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Empty,
+    MissingGplHeader,
+    InvalidLine { line: usize, content: String },
 }
 
 #[derive(Debug)]
@@ -65,18 +85,30 @@ impl From<flat::Error> for AsciiError {
     }
 }
 
+impl From<serde_json::Error> for AsciiError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<LinearError> for AsciiError {
+    fn from(value: LinearError) -> Self {
+        Self::Gradient(value)
+    }
+}
+
 impl From<Vec<AsciiError>> for AsciiError {
     fn from(value: Vec<AsciiError>) -> Self {
         Self::ManyErrors(value)
     }
 }
 
-pub trait IntoConvertNotCalledResult {
-    fn ok_or_ascii_err(&self) -> Result<&ImageBuffer<Rgb<u8>, Vec<u8>>, AsciiError>;
+pub trait IntoConvertNotCalledResult<T> {
+    fn ok_or_ascii_err(&self) -> Result<&T, AsciiError>;
 }
 
-impl IntoConvertNotCalledResult for Option<ImageBuffer<Rgb<u8>, Vec<u8>>> {
-    fn ok_or_ascii_err(&self) -> Result<&ImageBuffer<Rgb<u8>, Vec<u8>>, AsciiError> {
+impl<T> IntoConvertNotCalledResult<T> for Option<T> {
+    fn ok_or_ascii_err(&self) -> Result<&T, AsciiError> {
         self.as_ref().ok_or(AsciiError::ConvertNotCalled)
     }
 }
@@ -115,10 +147,28 @@ impl Display for AsciiError {
                 GroupedImageError::RowIndexOutOfBounds { index, row_len } => {
                     format!("Grouping the image for asciification went out of bounds at index: [{index}] and with row len: [{row_len}]")
                 }
+                GroupedImageError::TooSmall {
+                    width,
+                    height,
+                    char_box: (char_width, char_height),
+                } => format!(
+                    "The image of {width}x{height} pixels does not fill a single cell of {char_width}x{char_height} pixels."
+                ),
             },
             Self::ConvertNotCalled => {
                 "Convert was not called so there is no asciified Image.".into()
             }
+            Self::Json(json_error) => json_error.to_string(),
+            Self::Palette(palette_error) => match palette_error {
+                PaletteError::Empty => "The palette does not contain any colors.".into(),
+                PaletteError::MissingGplHeader => {
+                    "The palette is missing the \"GIMP Palette\" header.".into()
+                }
+                PaletteError::InvalidLine { line, content } => {
+                    format!("The palette has an invalid color in line [{line}]: [{content}]")
+                }
+            },
+            Self::Gradient(gradient_error) => format!("Invalid gradient: {gradient_error}"),
             Self::ManyErrors(errors) => {
                 // WARNING: could call some dangerous recursion
                 let len = errors.len();
@@ -0,0 +1,127 @@
+use enterpolation::{linear::Linear, Generator, Identity, Sorted};
+use image::Rgb;
+use palette::{LinSrgb, Srgb};
+use serde::{Deserialize, Serialize};
+
+use crate::{cell_grid::rgb_serde, error::AsciiError};
+
+type Curve = Linear<Sorted<Vec<f64>>, Vec<LinSrgb<f64>>, Identity>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GradientStop {
+    /// The luminance, between `0` and `1`, at which the gradient has this color.
+    pub position: f64,
+    #[serde(with = "rgb_serde")]
+    pub color: Rgb<u8>,
+}
+
+/// A gradient with any number of stops that maps the luminance of a cell to a
+/// color, interpolated in linear light. Serializes as the list of its stops.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Vec<GradientStop>", into = "Vec<GradientStop>")]
+pub struct ColorGradient {
+    stops: Vec<GradientStop>,
+    curve: Curve,
+}
+
+impl ColorGradient {
+    /// Creates a gradient from stops sorted by their position. Luminances before
+    /// the first or after the last stop take the color of that stop.
+    pub fn new(stops: Vec<GradientStop>) -> Result<Self, AsciiError> {
+        let mut curve_stops = stops.clone();
+        // the interpolation needs at least two stops, a single one is a flat color
+        if let [stop] = curve_stops[..] {
+            curve_stops.push(stop);
+        }
+        let curve = Linear::builder()
+            .elements(
+                curve_stops
+                    .iter()
+                    .map(|stop| to_linear(stop.color))
+                    .collect::<Vec<_>>(),
+            )
+            .knots(
+                curve_stops
+                    .iter()
+                    .map(|stop| stop.position)
+                    .collect::<Vec<_>>(),
+            )
+            .build()?;
+        Ok(Self { stops, curve })
+    }
+
+    /// Spreads the colors evenly from a luminance of `0` to `1`.
+    pub fn evenly_spaced(colors: &[Rgb<u8>]) -> Result<Self, AsciiError> {
+        let last = colors.len().saturating_sub(1).max(1) as f64;
+        Self::new(
+            colors
+                .iter()
+                .enumerate()
+                .map(|(i, color)| GradientStop {
+                    position: i as f64 / last,
+                    color: *color,
+                })
+                .collect(),
+        )
+    }
+
+    /// Black over purple and orange to white.
+    pub fn heatmap() -> Self {
+        Self::evenly_spaced(&[
+            Rgb([0, 0, 0]),
+            Rgb([110, 20, 140]),
+            Rgb([250, 140, 20]),
+            Rgb([255, 255, 255]),
+        ])
+        .unwrap()
+    }
+
+    /// Black over the green of old terminals to an almost white green.
+    pub fn matrix() -> Self {
+        Self::evenly_spaced(&[
+            Rgb([0, 0, 0]),
+            Rgb([0, 100, 30]),
+            Rgb([0, 255, 65]),
+            Rgb([200, 255, 210]),
+        ])
+        .unwrap()
+    }
+
+    pub fn stops(&self) -> &[GradientStop] {
+        &self.stops
+    }
+
+    /// The color of the gradient at the luminance, between `0` and `1`.
+    pub fn color(&self, luminance: f64) -> Rgb<u8> {
+        let (first, last) = (
+            self.stops[0].position,
+            self.stops[self.stops.len() - 1].position,
+        );
+        let color: Srgb<u8> = Srgb::from_linear(self.curve.gen(luminance.clamp(first, last)));
+        Rgb([color.red, color.green, color.blue])
+    }
+}
+
+impl PartialEq for ColorGradient {
+    fn eq(&self, other: &Self) -> bool {
+        self.stops == other.stops
+    }
+}
+
+impl TryFrom<Vec<GradientStop>> for ColorGradient {
+    type Error = AsciiError;
+
+    fn try_from(stops: Vec<GradientStop>) -> Result<Self, Self::Error> {
+        Self::new(stops)
+    }
+}
+
+impl From<ColorGradient> for Vec<GradientStop> {
+    fn from(gradient: ColorGradient) -> Self {
+        gradient.stops
+    }
+}
+
+fn to_linear(Rgb([r, g, b]): Rgb<u8>) -> LinSrgb<f64> {
+    Srgb::new(r, g, b).into_format::<f64>().into_linear()
+}
@@ -1,10 +1,13 @@
 use std::{sync::Arc, thread};
 
-use image::{GenericImageView, ImageBuffer, Rgb, SubImage};
+use image::{GenericImageView, Rgb, RgbImage, RgbaImage, SubImage};
+use palette::{LinSrgb, Srgb};
 
 use crate::{
-    asciifier::{convert_to_gray, get_adjusted_size},
+    asciifier::{convert_to_gray_with, get_adjusted_size, LuminanceModel},
+    chars::font_handler::CharacterBackground,
     error::AsciiError,
+    style::Whitening,
     Coverage,
 };
 
@@ -19,7 +22,10 @@ impl GroupedImage {
     pub fn new(
         group_width: usize,
         group_height: usize,
-        image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+        image: RgbaImage,
+        background: CharacterBackground,
+        whitening: Whitening,
+        luminance_model: LuminanceModel,
     ) -> Result<GroupedImage, AsciiError> {
         let (adjusted_width, adjusted_height) =
             get_adjusted_size(&image, &(group_width, group_height));
@@ -44,7 +50,12 @@ impl GroupedImage {
                             group_width as u32,
                             group_height as u32,
                         );
-                        row.push(PixelGroup::new(sub_image)?);
+                        row.push(PixelGroup::new(
+                            sub_image,
+                            background,
+                            whitening,
+                            luminance_model,
+                        )?);
                     }
                     Ok::<Vec<PixelGroup>, AsciiError>(row)
                 })
@@ -75,31 +86,65 @@ impl GroupedImage {
 #[derive(Debug, Clone)]
 pub(crate) struct PixelGroup {
     pub color: Rgb<u8>,
+    /// The amount of ink needed per square, which is the luminance of the group on
+    /// a black background and the inverted luminance on a white one.
     pub coverage: Coverage,
+    /// The average alpha of the group between `0` and `1`.
+    pub alpha: f64,
+    /// The average luminance of the group composited onto the canvas of the
+    /// character background, between `0` and `1`.
+    pub luminance: f64,
 }
 
 impl PixelGroup {
-    pub(crate) fn new(image: SubImage<&ImageBuffer<Rgb<u8>, Vec<u8>>>) -> Result<Self, AsciiError> {
-        let gray_image = convert_to_gray(&image.to_image());
-        let coverage =
+    pub(crate) fn new(
+        image: SubImage<&RgbaImage>,
+        background: CharacterBackground,
+        whitening: Whitening,
+        luminance_model: LuminanceModel,
+    ) -> Result<Self, AsciiError> {
+        // the coverage is taken from the group composited onto the canvas of the
+        // background, so transparent pixels need no ink, which like all blending
+        // happens in linear light
+        let Rgb([canvas_r, canvas_g, canvas_b]) = background.canvas_color();
+        let canvas = Srgb::new(canvas_r, canvas_g, canvas_b).into_linear::<f64>();
+        let composited = RgbImage::from_fn(image.width(), image.height(), |x, y| {
+            let [r, g, b, a] = image.get_pixel(x, y).0;
+            let a = a as f64 / 255.;
+            let color: Srgb<u8> =
+                Srgb::from_linear(Srgb::new(r, g, b).into_linear::<f64>() * a + canvas * (1. - a));
+            Rgb([color.red, color.green, color.blue])
+        });
+        let gray_image = convert_to_gray_with(&composited, luminance_model);
+        let luminance_coverage =
             Coverage::new(gray_image.view(0, 0, gray_image.width(), gray_image.height()))?;
+        let luminance = luminance_coverage.avg();
+        let coverage = luminance_coverage.from_func(|luminance| background.ink(luminance));
 
         let len = (image.width() * image.height()) as f64;
-        let (r, g, b) = image
-            .pixels()
-            .map(|(_, _, pixel)| [pixel.0[0] as f64, pixel.0[1] as f64, pixel.0[2] as f64])
-            .fold((0f64, 0f64, 0f64), |(r_sum, g_sum, b_sum), [r, g, b]| {
-                (r_sum + r, g_sum + g, b_sum + b)
-            });
-        let r = (r / len) as u8;
-        let g = (g / len) as u8;
-        let b = (b / len) as u8;
-
-        let max = Ord::max(Ord::max(r, g), b);
-
-        let add = ((255 - max) as f64 * (1. - coverage.avg())) as u8;
-        let color = [r + add, g + add, b + add].into();
-
-        Ok(Self { color, coverage })
+        // the color is averaged in linear light and weighted by alpha so that the
+        // invisible color of transparent pixels does not bleed into it
+        let (sum, alpha) = image.pixels().fold(
+            (LinSrgb::new(0., 0., 0.), 0.),
+            |(sum, alpha_sum), (_, _, pixel)| {
+                let [r, g, b, a] = pixel.0;
+                let a = a as f64 / 255.;
+                (
+                    sum + Srgb::new(r, g, b).into_linear::<f64>() * a,
+                    alpha_sum + a,
+                )
+            },
+        );
+        let average = if alpha > 0. { sum / alpha } else { sum };
+        let alpha = alpha / len;
+
+        let color = whitening.apply(average, coverage.avg(), background);
+
+        Ok(Self {
+            color,
+            coverage,
+            alpha,
+            luminance,
+        })
     }
 }
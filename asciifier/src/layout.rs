@@ -0,0 +1,363 @@
+use image::{
+    imageops::{self, FilterType},
+    Rgba, RgbaImage,
+};
+
+use crate::error::{AsciiError, GroupedImageError};
+
+/// How many cells the converted grid has.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GridSize {
+    /// As many cells as fit into the image at the height of the font.
+    #[default]
+    Native,
+    /// Exactly this many columns, with as many rows as keep the aspect ratio.
+    Columns(usize),
+    /// Exactly this many rows, with as many columns as keep the aspect ratio.
+    Rows(usize),
+    /// The largest grid keeping the aspect ratio that fits within `columns` x
+    /// `rows`.
+    Fit { columns: usize, rows: usize },
+}
+
+impl GridSize {
+    /// The number of columns and rows for an image of the size, or `None` for
+    /// [`GridSize::Native`].
+    pub fn cells(
+        &self,
+        (width, height): (u32, u32),
+        (char_width, char_height): (usize, usize),
+    ) -> Option<(usize, usize)> {
+        // the image size in cells, which are not square
+        let (image_columns, image_rows) = (
+            width as f64 / char_width as f64,
+            height as f64 / char_height as f64,
+        );
+        let scale = match *self {
+            GridSize::Native => return None,
+            GridSize::Columns(columns) => columns as f64 / image_columns,
+            GridSize::Rows(rows) => rows as f64 / image_rows,
+            GridSize::Fit { columns, rows } => {
+                (columns as f64 / image_columns).min(rows as f64 / image_rows)
+            }
+        };
+        let cells = |size: f64| (size * scale).round().max(1.) as usize;
+        Some(match *self {
+            GridSize::Columns(columns) => (columns.max(1), cells(image_rows)),
+            GridSize::Rows(rows) => (cells(image_columns), rows.max(1)),
+            _ => (cells(image_columns), cells(image_rows)),
+        })
+    }
+
+    /// Resamples the image to exactly fill the grid, after scaling its height by
+    /// `vertical_scale`. [`GridSize::Native`] only applies the scale.
+    pub(crate) fn resize(
+        &self,
+        image: RgbaImage,
+        char_box: (usize, usize),
+        vertical_scale: f64,
+        filter: FilterType,
+    ) -> Result<RgbaImage, AsciiError> {
+        let (width, height) = image.dimensions();
+        // an empty image has no aspect ratio to keep
+        if width == 0 || height == 0 {
+            return Err(too_small(width, height, char_box));
+        }
+        let scaled_height = (height as f64 * vertical_scale).round().max(1.) as u32;
+        Ok(match self.cells((width, scaled_height), char_box) {
+            Some((columns, rows)) => imageops::resize(
+                &image,
+                (columns * char_box.0) as u32,
+                (rows * char_box.1) as u32,
+                filter,
+            ),
+            None if scaled_height != height => {
+                imageops::resize(&image, width, scaled_height, filter)
+            }
+            None => image,
+        })
+    }
+}
+
+fn too_small(width: u32, height: u32, char_box: (usize, usize)) -> AsciiError {
+    AsciiError::GroupedImage(GroupedImageError::TooSmall {
+        width,
+        height,
+        char_box,
+    })
+}
+
+/// Where the image is placed when it is cropped or padded to whole cells.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Center,
+}
+
+impl Anchor {
+    /// The offset of a span of `inner` within `outer`, in either direction.
+    fn offset(&self, outer: u32, inner: u32) -> u32 {
+        match self {
+            Anchor::TopLeft => 0,
+            Anchor::Center => outer.abs_diff(inner) / 2,
+        }
+    }
+}
+
+/// What happens to the pixels at the right and bottom edge that do not fill a
+/// whole cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgePolicy {
+    /// Drops them, keeping the part of the image at the anchor.
+    Crop(Anchor),
+    /// Fills the rest of the last cells with the color.
+    Pad { color: Rgba<u8>, anchor: Anchor },
+    /// Fills the rest of the last cells by repeating the pixels at the edge.
+    Extend(Anchor),
+    /// Resamples the image to the closest number of whole cells.
+    Stretch,
+}
+
+impl Default for EdgePolicy {
+    fn default() -> Self {
+        EdgePolicy::Crop(Anchor::TopLeft)
+    }
+}
+
+impl EdgePolicy {
+    /// Fits the image to a whole number of cells, failing if it has none.
+    pub(crate) fn apply(
+        &self,
+        image: RgbaImage,
+        char_box: (usize, usize),
+        filter: FilterType,
+    ) -> Result<RgbaImage, AsciiError> {
+        let (width, height) = image.dimensions();
+        let (char_width, char_height) = (char_box.0 as u32, char_box.1 as u32);
+        if width == 0 || height == 0 {
+            return Err(too_small(width, height, char_box));
+        }
+        if width % char_width == 0 && height % char_height == 0 {
+            return Ok(image);
+        }
+        let (padded_width, padded_height) = (
+            width.div_ceil(char_width) * char_width,
+            height.div_ceil(char_height) * char_height,
+        );
+        let pad = |anchor: &Anchor, color: Rgba<u8>| {
+            let (x, y) = (
+                anchor.offset(padded_width, width),
+                anchor.offset(padded_height, height),
+            );
+            let mut padded = RgbaImage::from_pixel(padded_width, padded_height, color);
+            imageops::replace(&mut padded, &image, x as i64, y as i64);
+            (padded, x, y)
+        };
+        Ok(match self {
+            EdgePolicy::Crop(anchor) => {
+                let (cropped_width, cropped_height) = (
+                    width / char_width * char_width,
+                    height / char_height * char_height,
+                );
+                if cropped_width == 0 || cropped_height == 0 {
+                    return Err(too_small(width, height, char_box));
+                }
+                imageops::crop_imm(
+                    &image,
+                    anchor.offset(width, cropped_width),
+                    anchor.offset(height, cropped_height),
+                    cropped_width,
+                    cropped_height,
+                )
+                .to_image()
+            }
+            EdgePolicy::Pad { color, anchor } => pad(anchor, *color).0,
+            EdgePolicy::Extend(anchor) => {
+                let (mut padded, offset_x, offset_y) = pad(anchor, Rgba([0; 4]));
+                for (x, y, pixel) in padded.enumerate_pixels_mut() {
+                    let source_x = x.saturating_sub(offset_x).min(width - 1);
+                    let source_y = y.saturating_sub(offset_y).min(height - 1);
+                    *pixel = *image.get_pixel(source_x, source_y);
+                }
+                padded
+            }
+            EdgePolicy::Stretch => {
+                let cells = |size: u32, char_size: u32| {
+                    ((size as f64 / char_size as f64).round() as u32).max(1) * char_size
+                };
+                imageops::resize(
+                    &image,
+                    cells(width, char_width),
+                    cells(height, char_height),
+                    filter,
+                )
+            }
+        })
+    }
+
+    /// Moves the image by the offset relative to the grid, so the grid starts that
+    /// far into it. The policies dropping pixels crop them from the left and top,
+    /// the others add the rest of a cell in front as they would at the edge. The
+    /// result still has to be fitted with [`EdgePolicy::apply`].
+    pub(crate) fn shift(
+        &self,
+        image: &RgbaImage,
+        (x, y): (u32, u32),
+        (char_width, char_height): (usize, usize),
+    ) -> RgbaImage {
+        let (width, height) = image.dimensions();
+        if (x, y) == (0, 0) {
+            return image.clone();
+        }
+        let (pad_x, pad_y) = (
+            (char_width as u32 - x) % char_width as u32,
+            (char_height as u32 - y) % char_height as u32,
+        );
+        let padded = |color: Rgba<u8>| {
+            let mut padded = RgbaImage::from_pixel(width + pad_x, height + pad_y, color);
+            imageops::replace(&mut padded, image, pad_x as i64, pad_y as i64);
+            padded
+        };
+        match self {
+            // an offset past the image leaves it empty, which fails to fit
+            EdgePolicy::Crop(_) | EdgePolicy::Stretch => imageops::crop_imm(
+                image,
+                x,
+                y,
+                width.saturating_sub(x),
+                height.saturating_sub(y),
+            )
+            .to_image(),
+            EdgePolicy::Pad { color, .. } => padded(*color),
+            EdgePolicy::Extend(_) => {
+                let mut padded = padded(Rgba([0; 4]));
+                for (pixel_x, pixel_y, pixel) in padded.enumerate_pixels_mut() {
+                    let source_x = pixel_x.saturating_sub(pad_x).min(width - 1);
+                    let source_y = pixel_y.saturating_sub(pad_y).min(height - 1);
+                    *pixel = *image.get_pixel(source_x, source_y);
+                }
+                padded
+            }
+        }
+    }
+}
+
+/// A change to the geometry of the image, applied before it is filtered and
+/// converted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    /// Keeps only the rectangle, clipped to the image. Converting fails if less than
+    /// a single cell is left.
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Rotates clockwise by any angle in degrees, growing the image to fit all of it
+    /// and filling the uncovered corners with `fill`.
+    Rotate {
+        degrees: f32,
+        fill: Rgba<u8>,
+    },
+    FlipHorizontal,
+    FlipVertical,
+}
+
+impl Transform {
+    pub fn apply(&self, image: &RgbaImage) -> RgbaImage {
+        match *self {
+            Transform::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => imageops::crop_imm(image, x, y, width, height).to_image(),
+            Transform::Rotate90 => imageops::rotate90(image),
+            Transform::Rotate180 => imageops::rotate180(image),
+            Transform::Rotate270 => imageops::rotate270(image),
+            Transform::Rotate { degrees, fill } => rotate(image, degrees, fill),
+            Transform::FlipHorizontal => imageops::flip_horizontal(image),
+            Transform::FlipVertical => imageops::flip_vertical(image),
+        }
+    }
+}
+
+fn rotate(image: &RgbaImage, degrees: f32, fill: Rgba<u8>) -> RgbaImage {
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (rotated_width, rotated_height) = (
+        (width * cos.abs() + height * sin.abs()).round(),
+        (width * sin.abs() + height * cos.abs()).round(),
+    );
+    RgbaImage::from_fn(rotated_width as u32, rotated_height as u32, |x, y| {
+        // every pixel samples the source at its position rotated back around the
+        // centers of both images
+        let (x, y) = (
+            x as f32 + 0.5 - rotated_width / 2.,
+            y as f32 + 0.5 - rotated_height / 2.,
+        );
+        let source_x = x * cos + y * sin + width / 2. - 0.5;
+        let source_y = -x * sin + y * cos + height / 2. - 0.5;
+        imageops::interpolate_bilinear(image, source_x, source_y).unwrap_or(fill)
+    })
+}
+
+/// Tries shifting the grid by fractions of a cell, and optionally scaling the image
+/// a little, keeping the alignment whose cells match their characters best. This
+/// keeps thin lines from being split between two cells, at the cost of matching
+/// the whole image once for every alignment tried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignmentSearch {
+    /// The number of offsets tried along each axis, spread evenly over one cell.
+    pub steps: usize,
+    /// The scales of the image tried at every offset, `1` being its size. Only used
+    /// with [`GridSize::Native`], since the other sizes fix the number of cells.
+    pub scales: Vec<f64>,
+}
+
+impl Default for AlignmentSearch {
+    fn default() -> Self {
+        Self {
+            steps: 4,
+            scales: vec![1.],
+        }
+    }
+}
+
+impl AlignmentSearch {
+    pub(crate) fn scales(&self, grid_size: GridSize) -> &[f64] {
+        match grid_size {
+            GridSize::Native => &self.scales,
+            _ => &[1.],
+        }
+    }
+
+    /// The offsets of the grid origin into the image, spread over one cell.
+    pub(crate) fn offsets(
+        &self,
+        (char_width, char_height): (usize, usize),
+    ) -> impl Iterator<Item = (u32, u32)> {
+        let steps = self.steps;
+        let offsets = move |char_size: usize| {
+            let steps = steps.clamp(1, char_size);
+            (0..steps).map(move |step| (step * char_size / steps) as u32)
+        };
+        offsets(char_height).flat_map(move |y| offsets(char_width).map(move |x| (x, y)))
+    }
+}
+
+/// Scales the image by the factor, keeping at least one pixel in each direction.
+pub(crate) fn scale(image: &RgbaImage, factor: f64, filter: FilterType) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    imageops::resize(
+        image,
+        ((width as f64 * factor).round() as u32).max(1),
+        ((height as f64 * factor).round() as u32).max(1),
+        filter,
+    )
+}
@@ -1,12 +1,24 @@
 use error::{AsciiError, FontParseErrors};
 use image::{GenericImageView, ImageBuffer, Luma, SubImage};
+use serde::{Deserialize, Serialize};
 
 pub mod asciifier;
+pub mod cell_grid;
 pub mod chars;
+pub mod color_palette;
+pub mod dither;
 pub mod error;
+pub mod gradient;
 pub mod grouped_image;
+pub mod layout;
+pub mod preprocess;
+pub mod render;
+pub mod style;
+pub mod subcell;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// The brightness of a glyph or pixel group sampled on a 4x4 grid, each square
+/// holding a value between `0` and `1`.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Coverage {
     squares: [f64; 16],
 }
@@ -45,6 +57,14 @@ impl Coverage {
         Ok(Self { squares })
     }
 
+    pub(crate) fn from_squares(squares: [f64; 16]) -> Self {
+        Self { squares }
+    }
+
+    pub fn squares(&self) -> &[f64; 16] {
+        &self.squares
+    }
+
     pub fn dist(&self, other: &Self) -> f64 {
         (0..16)
             .map(|i| (self.squares[i] - other.squares[i]).powi(2))
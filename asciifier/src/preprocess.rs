@@ -0,0 +1,253 @@
+use image::{imageops, Rgba, RgbaImage};
+
+/// A single adjustment applied to the image before it is grouped into cells. The
+/// alpha of the pixels is never changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// `brightness` is added to every channel and should be between `-1` and `1`,
+    /// `contrast` scales the channels around the middle gray, `1` keeps them as is.
+    BrightnessContrast { brightness: f32, contrast: f32 },
+    /// Raises every channel to the power of `1 / gamma`, so values above `1`
+    /// brighten the midtones and values below darken them.
+    Gamma(f32),
+    /// Stretches the channels so that `black` becomes `0` and `white` becomes `255`,
+    /// then applies the `gamma` like [`Filter::Gamma`].
+    Levels { black: u8, white: u8, gamma: f32 },
+    /// Scales the distance of every color to its gray, `0` removes all color and
+    /// `1` keeps it as is.
+    Saturation(f32),
+    /// Spreads the brightness evenly over the whole range using the histogram of
+    /// the luma.
+    Equalize,
+    /// Contrast limited adaptive histogram equalization, which equalizes each of the
+    /// `tiles` x `tiles` regions on its own and blends between them. `clip_limit`
+    /// limits how far the contrast is raised, as a multiple of the average count of
+    /// the histogram, where `1` leaves the image almost unchanged.
+    Clahe { tiles: u32, clip_limit: f32 },
+    /// Sharpens the image by adding the difference to a blurred copy, which is only
+    /// done where the difference exceeds `threshold`.
+    Unsharp { sigma: f32, threshold: i32 },
+    /// Smooths noise while keeping edges, by averaging every pixel with the pixels
+    /// within `radius` weighted by their distance and their difference in color.
+    Bilateral {
+        radius: u32,
+        sigma_color: f32,
+        sigma_space: f32,
+    },
+}
+
+impl Filter {
+    pub fn apply(&self, image: &RgbaImage) -> RgbaImage {
+        match *self {
+            Filter::BrightnessContrast {
+                brightness,
+                contrast,
+            } => map_channels(image, |value| (value - 0.5) * contrast + 0.5 + brightness),
+            Filter::Gamma(gamma) => map_channels(image, |value| value.powf(1. / gamma)),
+            Filter::Levels {
+                black,
+                white,
+                gamma,
+            } => {
+                let (black, white) = (black as f32 / 255., white as f32 / 255.);
+                let range = (white - black).max(f32::EPSILON);
+                map_channels(image, |value| {
+                    ((value - black) / range).clamp(0., 1.).powf(1. / gamma)
+                })
+            }
+            Filter::Saturation(saturation) => {
+                let mut image = image.clone();
+                image.pixels_mut().for_each(|Rgba([r, g, b, _])| {
+                    let luma = luma(*r, *g, *b) as f32;
+                    [r, g, b].into_iter().for_each(|channel| {
+                        *channel =
+                            (luma + (*channel as f32 - luma) * saturation).clamp(0., 255.) as u8
+                    });
+                });
+                image
+            }
+            Filter::Equalize => {
+                let lut = equalization_lut(&histogram(image.pixels()), None);
+                map_luma(image, |_, _, luma| lut[luma as usize] as f32)
+            }
+            Filter::Clahe { tiles, clip_limit } => clahe(image, tiles.max(1), clip_limit),
+            Filter::Unsharp { sigma, threshold } => {
+                // sharpening the alpha would change which cells count as transparent
+                let mut sharpened = imageops::unsharpen(image, sigma, threshold);
+                sharpened
+                    .pixels_mut()
+                    .zip(image.pixels())
+                    .for_each(|(Rgba([.., alpha]), Rgba([.., original]))| *alpha = *original);
+                sharpened
+            }
+            Filter::Bilateral {
+                radius,
+                sigma_color,
+                sigma_space,
+            } => bilateral(image, radius, sigma_color, sigma_space),
+        }
+    }
+}
+
+/// Applies the function to every color channel through a lookup table, with the
+/// channels passed in and expected between `0` and `1`.
+fn map_channels(image: &RgbaImage, func: impl Fn(f32) -> f32) -> RgbaImage {
+    let lut: [u8; 256] =
+        std::array::from_fn(|i| (func(i as f32 / 255.).clamp(0., 1.) * 255.).round() as u8);
+    let mut image = image.clone();
+    image.pixels_mut().for_each(|Rgba([r, g, b, _])| {
+        [r, g, b]
+            .into_iter()
+            .for_each(|channel| *channel = lut[*channel as usize]);
+    });
+    image
+}
+
+/// Replaces the luma of every pixel by the one the function returns for its
+/// position and luma, keeping the hue and the saturation.
+fn map_luma(image: &RgbaImage, func: impl Fn(u32, u32, u8) -> f32) -> RgbaImage {
+    let mut image = image.clone();
+    for (x, y, Rgba([r, g, b, _])) in image.enumerate_pixels_mut() {
+        let luma = luma(*r, *g, *b);
+        let [new_r, new_g, new_b] = with_luma([*r, *g, *b], luma, func(x, y, luma));
+        [*r, *g, *b] = [new_r, new_g, new_b];
+    }
+    image
+}
+
+/// Scales the color to the new luma. Colors that would leave the range are moved
+/// towards the gray of that luma instead, which keeps the hue.
+fn with_luma(color: [u8; 3], luma: u8, new_luma: f32) -> [u8; 3] {
+    let new_luma = new_luma.clamp(0., 255.);
+    if luma == 0 {
+        return [new_luma.round() as u8; 3];
+    }
+    let scaled = color.map(|channel| channel as f32 * new_luma / luma as f32);
+    let max = scaled.iter().copied().fold(0., f32::max);
+    let factor = if max > 255. {
+        (255. - new_luma) / (max - new_luma)
+    } else {
+        1.
+    };
+    scaled.map(|channel| {
+        (new_luma + (channel - new_luma) * factor)
+            .round()
+            .clamp(0., 255.) as u8
+    })
+}
+
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) as u8
+}
+
+fn histogram<'a>(pixels: impl Iterator<Item = &'a Rgba<u8>>) -> [u32; 256] {
+    let mut histogram = [0; 256];
+    pixels.for_each(|Rgba([r, g, b, _])| histogram[luma(*r, *g, *b) as usize] += 1);
+    histogram
+}
+
+/// The lookup table equalizing the histogram, whose counts are clipped at
+/// `clip_limit` times the average count first if given.
+fn equalization_lut(histogram: &[u32; 256], clip_limit: Option<f32>) -> [u8; 256] {
+    let total = histogram.iter().sum::<u32>();
+    if total == 0 {
+        return std::array::from_fn(|i| i as u8);
+    }
+    let mut counts = histogram.map(|count| count as f32);
+    if let Some(clip_limit) = clip_limit {
+        let limit = (total as f32 / 256. * clip_limit).max(1.);
+        let excess = counts
+            .iter_mut()
+            .map(|count| {
+                let excess = (*count - limit).max(0.);
+                *count -= excess;
+                excess
+            })
+            .sum::<f32>();
+        // the clipped counts are spread evenly over the whole histogram
+        counts.iter_mut().for_each(|count| *count += excess / 256.);
+    }
+    let mut lut = [0; 256];
+    let mut sum = 0.;
+    for (value, count) in counts.iter().enumerate() {
+        sum += count;
+        lut[value] = (sum / total as f32 * 255.).round().clamp(0., 255.) as u8;
+    }
+    lut
+}
+
+fn clahe(image: &RgbaImage, tiles: u32, clip_limit: f32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let (tile_width, tile_height) = (width.div_ceil(tiles).max(1), height.div_ceil(tiles).max(1));
+    let luts = (0..tiles)
+        .map(|tile_y| {
+            (0..tiles)
+                .map(|tile_x| {
+                    let (x, y) = (tile_x * tile_width, tile_y * tile_height);
+                    let tile = imageops::crop_imm(
+                        image,
+                        x.min(width),
+                        y.min(height),
+                        tile_width.min(width.saturating_sub(x)),
+                        tile_height.min(height.saturating_sub(y)),
+                    )
+                    .to_image();
+                    equalization_lut(&histogram(tile.pixels()), Some(clip_limit))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // every pixel blends the tables of the four tiles whose centers surround it
+    let neighbours = |position: u32, size: u32| {
+        let tile = (position as f32 + 0.5) / size as f32 - 0.5;
+        let low = tile.floor().clamp(0., (tiles - 1) as f32);
+        let high = (low + 1.).min((tiles - 1) as f32);
+        let factor = (tile - low).clamp(0., 1.);
+        (low as usize, high as usize, factor)
+    };
+    map_luma(image, |x, y, luma| {
+        let (left, right, factor_x) = neighbours(x, tile_width);
+        let (top, bottom, factor_y) = neighbours(y, tile_height);
+        let value = |lut: &[u8; 256]| lut[luma as usize] as f32;
+        let top_value =
+            value(&luts[top][left]) * (1. - factor_x) + value(&luts[top][right]) * factor_x;
+        let bottom_value =
+            value(&luts[bottom][left]) * (1. - factor_x) + value(&luts[bottom][right]) * factor_x;
+        top_value * (1. - factor_y) + bottom_value * factor_y
+    })
+}
+
+fn bilateral(image: &RgbaImage, radius: u32, sigma_color: f32, sigma_space: f32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let radius = radius as i64;
+    let space_factor = -1. / (2. * sigma_space * sigma_space).max(f32::EPSILON);
+    let color_factor = -1. / (2. * sigma_color * sigma_color).max(f32::EPSILON);
+    RgbaImage::from_fn(width, height, |x, y| {
+        let Rgba([r, g, b, a]) = *image.get_pixel(x, y);
+        let center = [r, g, b].map(|channel| channel as f32);
+        let mut sum = [0f32; 3];
+        let mut weight_sum = 0.;
+        for offset_y in -radius..=radius {
+            for offset_x in -radius..=radius {
+                let (neighbour_x, neighbour_y) = (x as i64 + offset_x, y as i64 + offset_y);
+                if neighbour_x < 0
+                    || neighbour_y < 0
+                    || neighbour_x >= width as i64
+                    || neighbour_y >= height as i64
+                {
+                    continue;
+                }
+                let Rgba([r, g, b, _]) = *image.get_pixel(neighbour_x as u32, neighbour_y as u32);
+                let color = [r, g, b].map(|channel| channel as f32);
+                let space = (offset_x * offset_x + offset_y * offset_y) as f32;
+                let difference = (0..3).map(|i| (color[i] - center[i]).powi(2)).sum::<f32>();
+                let weight = (space * space_factor + difference * color_factor).exp();
+                (0..3).for_each(|i| sum[i] += color[i] * weight);
+                weight_sum += weight;
+            }
+        }
+        let [r, g, b] = sum.map(|sum| (sum / weight_sum).round().clamp(0., 255.) as u8);
+        Rgba([r, g, b, a])
+    })
+}
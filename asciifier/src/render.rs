@@ -0,0 +1,22 @@
+pub mod ansi;
+pub mod html;
+pub mod svg;
+
+use ansi::AnsiOptions;
+use html::HtmlOptions;
+use image::ImageFormat;
+use svg::SvgOptions;
+
+/// The formats [`ImageBuilder::write_to`](crate::asciifier::ImageBuilder::write_to)
+/// is able to write a converted image in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputFormat {
+    /// The rasterized image encoded in any format supported by the `image` crate.
+    Image(ImageFormat),
+    /// The chosen characters as plain text, one line per row.
+    Text,
+    Ansi(AnsiOptions),
+    Html(HtmlOptions),
+    Svg(SvgOptions),
+    Json,
+}
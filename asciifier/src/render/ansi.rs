@@ -0,0 +1,232 @@
+use std::{fmt::Write, sync::OnceLock};
+
+use image::Rgb;
+use palette::{color_difference::Ciede2000, IntoColor, Lab, Srgb};
+
+use crate::cell_grid::{Cell, CellGrid};
+
+/// 4x4 Bayer matrix used for the ordered dither across cells.
+pub(crate) const BAYER_4X4: [[u8; 4]; 4] =
+    [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// The 16 basic colors as xterm draws them by default. Most terminals let the user
+/// theme these, so they are only an approximation of what will be displayed.
+const BASIC_16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// The intensities of the six steps of the xterm 6x6x6 color cube.
+const XTERM_CUBE_STEPS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AnsiColoring {
+    /// Only the characters are colored, the terminal background shows through.
+    #[default]
+    Foreground,
+    /// The characters are colored and the background of every cell is painted with
+    /// the cells background color.
+    ForegroundAndBackground,
+}
+
+/// The set of colors the terminal is able to display.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AnsiColorMode {
+    /// 24-bit colors, every cell is drawn with its exact color.
+    #[default]
+    TrueColor,
+    /// The xterm 256 color palette. Only the color cube and the grayscale ramp are
+    /// used since the first 16 entries depend on the terminals theme.
+    Xterm256,
+    /// The 8 basic colors and their bright variants.
+    Basic16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnsiOptions {
+    pub coloring: AnsiColoring,
+    pub color_mode: AnsiColorMode,
+    /// Applies an ordered dither across cells before mapping the colors to the
+    /// palette of the `color_mode`. Has no effect with [`AnsiColorMode::TrueColor`].
+    pub dither: bool,
+    /// Wraps runs of neighbouring cells with the same colors in a single escape
+    /// sequence instead of one per character.
+    pub coalesce: bool,
+}
+
+impl Default for AnsiOptions {
+    fn default() -> Self {
+        Self {
+            coloring: AnsiColoring::default(),
+            color_mode: AnsiColorMode::default(),
+            dither: false,
+            coalesce: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TermColor {
+    TrueColor(Rgb<u8>),
+    Xterm256(u8),
+    Basic16(u8),
+}
+
+impl TermColor {
+    fn new(color: Rgb<u8>, (col, row): (usize, usize), options: &AnsiOptions) -> Self {
+        let color = if options.dither {
+            dither(color, (col, row), options.color_mode)
+        } else {
+            color
+        };
+        match options.color_mode {
+            AnsiColorMode::TrueColor => Self::TrueColor(color),
+            AnsiColorMode::Xterm256 => Self::Xterm256(nearest(xterm_256_palette(), color)),
+            AnsiColorMode::Basic16 => Self::Basic16(nearest(basic_16_palette(), color)),
+        }
+    }
+
+    /// The SGR sequence selecting this color for the foreground or background.
+    fn sgr(&self, background: bool) -> String {
+        let layer = if background { 48 } else { 38 };
+        match *self {
+            Self::TrueColor(Rgb([r, g, b])) => format!("\x1B[{layer};2;{r};{g};{b}m"),
+            Self::Xterm256(index) => format!("\x1B[{layer};5;{index}m"),
+            Self::Basic16(index) => {
+                let code = match (background, index < 8) {
+                    (false, true) => 30 + index,
+                    (false, false) => 90 + index - 8,
+                    (true, true) => 40 + index,
+                    (true, false) => 100 + index - 8,
+                };
+                format!("\x1B[{code}m")
+            }
+        }
+    }
+}
+
+struct AnsiCell {
+    character: char,
+    foreground: TermColor,
+    background: Option<TermColor>,
+}
+
+/// Renders the grid as text colored with ANSI escape sequences, one line per row
+/// of the grid.
+pub fn to_ansi(grid: &CellGrid, options: &AnsiOptions) -> String {
+    let mut ansi = String::new();
+    for (row_i, row) in grid.rows().iter().enumerate() {
+        let row = row
+            .iter()
+            .enumerate()
+            .map(|(col_i, cell)| ansi_cell(cell, (col_i, row_i), options))
+            .collect::<Vec<_>>();
+        for run in runs(&row, options) {
+            let text = run.iter().map(|cell| cell.character).collect::<String>();
+            write_run(&mut ansi, &text, run[0].foreground, run[0].background);
+        }
+        ansi.push('\n');
+    }
+    ansi
+}
+
+fn ansi_cell(cell: &Cell, position: (usize, usize), options: &AnsiOptions) -> AnsiCell {
+    let background = match options.coloring {
+        AnsiColoring::Foreground => None,
+        AnsiColoring::ForegroundAndBackground => {
+            Some(TermColor::new(cell.background, position, options))
+        }
+    };
+    AnsiCell {
+        character: cell.character,
+        foreground: TermColor::new(cell.color, position, options),
+        background,
+    }
+}
+
+fn runs<'a>(row: &'a [AnsiCell], options: &AnsiOptions) -> Vec<&'a [AnsiCell]> {
+    if !options.coalesce {
+        return row.chunks(1).collect();
+    }
+    row.chunk_by(|a, b| a.foreground == b.foreground && a.background == b.background)
+        .collect()
+}
+
+/// Writes the colors of the run followed by its text and a single reset.
+fn write_run(ansi: &mut String, text: &str, foreground: TermColor, background: Option<TermColor>) {
+    // writing into a String can not fail
+    let _ = write!(
+        ansi,
+        "{}{}{text}\x1B[0m",
+        foreground.sgr(false),
+        background.map(|bg| bg.sgr(true)).unwrap_or_default()
+    );
+}
+
+/// Offsets the color by the threshold of the Bayer matrix at the cells position,
+/// scaled to roughly the distance between neighbouring palette entries.
+fn dither(Rgb(color): Rgb<u8>, (col, row): (usize, usize), mode: AnsiColorMode) -> Rgb<u8> {
+    let spread = match mode {
+        AnsiColorMode::TrueColor => return Rgb(color),
+        AnsiColorMode::Xterm256 => 40.,
+        AnsiColorMode::Basic16 => 96.,
+    };
+    let threshold = (BAYER_4X4[row % 4][col % 4] as f64 + 0.5) / 16. - 0.5;
+    Rgb(color.map(|channel| (channel as f64 + threshold * spread).clamp(0., 255.) as u8))
+}
+
+fn nearest(palette: &[(u8, Lab)], Rgb(color): Rgb<u8>) -> u8 {
+    let lab = to_lab(color);
+    palette
+        .iter()
+        .map(|(index, entry)| (*index, entry.difference(lab)))
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(index, _)| index)
+        .unwrap()
+}
+
+pub(crate) fn to_lab([r, g, b]: [u8; 3]) -> Lab {
+    Srgb::new(r, g, b).into_format::<f32>().into_color()
+}
+
+fn xterm_256_palette() -> &'static [(u8, Lab)] {
+    static PALETTE: OnceLock<Vec<(u8, Lab)>> = OnceLock::new();
+    PALETTE.get_or_init(|| {
+        let cube = (0..216).map(|i| {
+            let (r, g, b) = (i / 36, (i / 6) % 6, i % 6);
+            let color = [
+                XTERM_CUBE_STEPS[r],
+                XTERM_CUBE_STEPS[g],
+                XTERM_CUBE_STEPS[b],
+            ];
+            (16 + i as u8, to_lab(color))
+        });
+        let grays = (0..24u8).map(|i| (232 + i, to_lab([8 + i * 10; 3])));
+        cube.chain(grays).collect()
+    })
+}
+
+fn basic_16_palette() -> &'static [(u8, Lab)] {
+    static PALETTE: OnceLock<Vec<(u8, Lab)>> = OnceLock::new();
+    PALETTE.get_or_init(|| {
+        BASIC_16
+            .iter()
+            .enumerate()
+            .map(|(index, color)| (index as u8, to_lab(*color)))
+            .collect()
+    })
+}
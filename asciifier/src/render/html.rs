@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::Rgb;
+
+use crate::cell_grid::{Cell, CellGrid};
+
+pub(crate) const FONT_FAMILY: &str = "asciifier";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlOptions {
+    /// Color of the page behind the `<pre>` block.
+    pub background: Rgb<u8>,
+    /// Embeds the font used for the conversion as a base64 `@font-face`, without it
+    /// the browsers monospace font is used.
+    pub embed_font: bool,
+    pub title: String,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        Self {
+            background: Rgb([0, 0, 0]),
+            embed_font: true,
+            title: "asciified image".into(),
+        }
+    }
+}
+
+/// Renders the grid as a standalone html document containing a single `<pre>`
+/// block, with one colored `<span>` per run of cells with the same colors.
+pub fn to_html(
+    grid: &CellGrid,
+    font: &FontArc,
+    font_height: usize,
+    options: &HtmlOptions,
+) -> String {
+    let (char_width, line_height) = grid.char_box();
+    let font_size = font_size(font, font_height);
+    let font_face = if options.embed_font {
+        font_face(font)
+    } else {
+        String::new()
+    };
+    let font_family = if options.embed_font {
+        format!("\"{FONT_FAMILY}\", monospace")
+    } else {
+        "monospace".into()
+    };
+
+    let mut html = String::new();
+    // writing into a String can not fail
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+{font_face}body {{
+  margin: 0;
+  background-color: {background};
+}}
+pre {{
+  margin: 0;
+  font-family: {font_family};
+  font-size: {font_size}px;
+  line-height: {line_height}px;
+  letter-spacing: calc({char_width}px - 1ch);
+}}
+</style>
+</head>
+<body>
+<pre>"#,
+        title = escape(&options.title),
+        background = css_color(options.background),
+    );
+
+    for row in grid.rows() {
+        for run in row.chunk_by(|a, b| a.color == b.color && a.background == b.background) {
+            write_span(&mut html, run, options.background);
+        }
+        html.push('\n');
+    }
+
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+fn write_span(html: &mut String, run: &[Cell], page_background: Rgb<u8>) {
+    let Cell {
+        color, background, ..
+    } = run[0];
+    let text = escape(&run.iter().map(|cell| cell.character).collect::<String>());
+    let _ = if background == page_background {
+        write!(
+            html,
+            r#"<span style="color:{}">{text}</span>"#,
+            css_color(color)
+        )
+    } else {
+        write!(
+            html,
+            r#"<span style="color:{};background-color:{}">{text}</span>"#,
+            css_color(color),
+            css_color(background)
+        )
+    };
+}
+
+/// The size of the em of the font at the `PxScale` the glyphs were rasterized with,
+/// since ab_glyph scales the height from descent to ascent instead.
+pub(crate) fn font_size(font: &FontArc, font_height: usize) -> f32 {
+    let scale = font
+        .as_scaled(PxScale::from(font_height as f32))
+        .scale_factor();
+    font.units_per_em()
+        .map_or(font_height as f32, |units_per_em| {
+            units_per_em * scale.vertical
+        })
+}
+
+pub(crate) fn font_face(font: &FontArc) -> String {
+    let data = font.font_data();
+    let (mime, format) = if data.starts_with(b"OTTO") {
+        ("font/otf", "opentype")
+    } else {
+        ("font/ttf", "truetype")
+    };
+    format!(
+        "@font-face {{\n  font-family: \"{FONT_FAMILY}\";\n  src: url(data:{mime};base64,{}) format(\"{format}\");\n}}\n",
+        STANDARD.encode(data)
+    )
+}
+
+pub(crate) fn css_color(Rgb([r, g, b]): Rgb<u8>) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
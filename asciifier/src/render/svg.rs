@@ -0,0 +1,261 @@
+use std::{collections::HashMap, fmt::Write};
+
+use ab_glyph::{Font, FontArc, OutlineCurve, Point, PxScale, ScaleFont};
+use image::Rgb;
+
+use crate::{cell_grid::CellGrid, chars::font_handler::CharAlignment};
+
+use super::html::{css_color, escape, font_face, font_size, FONT_FAMILY};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SvgGlyphs {
+    /// Every cell is a `<text>` element, the text stays selectable but depends on
+    /// the font being available to the viewer.
+    #[default]
+    Text,
+    /// Every cell references the outline of its glyph as a `<path>`, which looks the
+    /// same everywhere but can not be selected as text.
+    Outline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SvgOptions {
+    pub glyphs: SvgGlyphs,
+    /// Embeds the font used for the conversion as a base64 `@font-face`. Only used
+    /// with [`SvgGlyphs::Text`] since outlines do not need the font.
+    pub embed_font: bool,
+    /// Color of the canvas behind the cells, transparent if `None`.
+    pub background: Option<Rgb<u8>>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            glyphs: SvgGlyphs::default(),
+            embed_font: true,
+            background: Some(Rgb([0, 0, 0])),
+        }
+    }
+}
+
+/// Renders the grid as an svg document in which every cell takes up the same
+/// `char_box` as in the rasterized image, so that both line up exactly.
+pub fn to_svg(
+    grid: &CellGrid,
+    font: &FontArc,
+    font_height: usize,
+    alignment: CharAlignment,
+    options: &SvgOptions,
+) -> String {
+    let (char_width, char_height) = grid.char_box();
+    let (width, height) = (
+        char_width * grid.num_columns(),
+        char_height * grid.num_rows(),
+    );
+
+    let mut svg = String::new();
+    // writing into a String can not fail
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    );
+    if let Some(background) = options.background {
+        let _ = writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            css_color(background)
+        );
+    }
+    write_cell_backgrounds(&mut svg, grid, options.background);
+
+    match options.glyphs {
+        SvgGlyphs::Text => write_text(&mut svg, grid, font, font_height, alignment, options),
+        SvgGlyphs::Outline => write_outlines(&mut svg, grid, font, font_height, alignment),
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Paints the background of runs of cells whose background differs from the
+/// canvas.
+fn write_cell_backgrounds(svg: &mut String, grid: &CellGrid, canvas: Option<Rgb<u8>>) {
+    let (char_width, char_height) = grid.char_box();
+    for (row_i, row) in grid.rows().iter().enumerate() {
+        let mut col_i = 0;
+        for run in row.chunk_by(|a, b| a.background == b.background) {
+            let background = run[0].background;
+            if Some(background) != canvas {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{}" height="{char_height}" fill="{}"/>"#,
+                    col_i * char_width,
+                    row_i * char_height,
+                    run.len() * char_width,
+                    css_color(background)
+                );
+            }
+            col_i += run.len();
+        }
+    }
+}
+
+fn write_text(
+    svg: &mut String,
+    grid: &CellGrid,
+    font: &FontArc,
+    font_height: usize,
+    alignment: CharAlignment,
+    options: &SvgOptions,
+) {
+    let (char_width, char_height) = grid.char_box();
+    let font_family = if options.embed_font {
+        let _ = writeln!(svg, "<style>\n{}</style>", font_face(font));
+        format!("'{FONT_FAMILY}', monospace")
+    } else {
+        "monospace".into()
+    };
+    let (anchor, offset) = match alignment {
+        CharAlignment::Left => ("start", 0.),
+        CharAlignment::Center => ("middle", char_width as f32 / 2.),
+        CharAlignment::Right => ("end", char_width as f32),
+    };
+    let font_size = font_size(font, font_height);
+    let descent = font.as_scaled(PxScale::from(font_height as f32)).descent();
+    let _ = writeln!(
+        svg,
+        r#"<g font-family="{font_family}" font-size="{font_size}" text-anchor="{anchor}" xml:space="preserve">"#
+    );
+    for (row_i, row) in grid.rows().iter().enumerate() {
+        // the glyphs are placed on the bottom of their box in the rasterized image,
+        // so the baseline is raised by the descent to keep descenders in the cell
+        let y = ((row_i + 1) * char_height) as f32 + descent;
+        let mut col_i = 0;
+        for run in row.chunk_by(|a, b| a.color == b.color) {
+            let xs = (col_i..col_i + run.len())
+                .map(|col| format!("{}", col as f32 * char_width as f32 + offset))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let text = escape(&run.iter().map(|cell| cell.character).collect::<String>());
+            let _ = writeln!(
+                svg,
+                r#"<text x="{xs}" y="{y}" fill="{}">{text}</text>"#,
+                css_color(run[0].color)
+            );
+            col_i += run.len();
+        }
+    }
+    svg.push_str("</g>\n");
+}
+
+fn write_outlines(
+    svg: &mut String,
+    grid: &CellGrid,
+    font: &FontArc,
+    font_height: usize,
+    alignment: CharAlignment,
+) {
+    let (char_width, char_height) = grid.char_box();
+
+    let mut glyph_ids = HashMap::new();
+    svg.push_str("<defs>\n");
+    for cell in grid.rows().iter().flatten() {
+        if glyph_ids.contains_key(&cell.character) {
+            continue;
+        }
+        let path = glyph_path(
+            font,
+            cell.character,
+            font_height,
+            (char_width, char_height),
+            alignment,
+        );
+        let id = path.map(|path| {
+            let id = format!("g{}", glyph_ids.len());
+            let _ = writeln!(svg, r#"<path id="{id}" d="{path}"/>"#);
+            id
+        });
+        glyph_ids.insert(cell.character, id);
+    }
+    svg.push_str("</defs>\n");
+
+    for (row_i, row) in grid.rows().iter().enumerate() {
+        for (col_i, cell) in row.iter().enumerate() {
+            if let Some(Some(id)) = glyph_ids.get(&cell.character) {
+                let _ = writeln!(
+                    svg,
+                    r##"<use xlink:href="#{id}" x="{}" y="{}" fill="{}"/>"##,
+                    col_i * char_width,
+                    row_i * char_height,
+                    css_color(cell.color)
+                );
+            }
+        }
+    }
+}
+
+/// Returns the svg path data of the glyph placed inside its char box the same way
+/// [`RasterizedChar`](crate::chars::char::RasterizedChar) places it, or `None` if
+/// the glyph has no outline.
+fn glyph_path(
+    font: &FontArc,
+    character: char,
+    font_height: usize,
+    (box_width, box_height): (usize, usize),
+    alignment: CharAlignment,
+) -> Option<String> {
+    let outline = font.outline(font.glyph_id(character))?;
+    let scale = font
+        .as_scaled(PxScale::from(font_height as f32))
+        .scale_factor();
+    // the outline is in font units with the y axis pointing up, which is why the
+    // `min` of its bounds holds the top and `max` the bottom of the glyph
+    let bounds = outline.bounds;
+    let width = (bounds.max.x - bounds.min.x) * scale.horizontal;
+    let height = (bounds.min.y - bounds.max.y) * scale.vertical;
+
+    let offset_x = match alignment {
+        CharAlignment::Left => 0.,
+        CharAlignment::Center => (box_width as f32 - width) / 2.,
+        CharAlignment::Right => box_width as f32 - width,
+    };
+    let offset_y = box_height as f32 - height;
+    let to_px = |point: Point| {
+        (
+            (point.x - bounds.min.x) * scale.horizontal + offset_x,
+            (bounds.min.y - point.y) * scale.vertical + offset_y,
+        )
+    };
+
+    let mut path = String::new();
+    let mut last = None;
+    for curve in &outline.curves {
+        let (start, end) = match curve {
+            OutlineCurve::Line(start, end) => (*start, *end),
+            OutlineCurve::Quad(start, _, end) => (*start, *end),
+            OutlineCurve::Cubic(start, _, _, end) => (*start, *end),
+        };
+        if last != Some(start) {
+            let (x, y) = to_px(start);
+            let _ = write!(path, "M{x:.2} {y:.2}");
+        }
+        let _ = match curve {
+            OutlineCurve::Line(_, end) => {
+                let (x, y) = to_px(*end);
+                write!(path, "L{x:.2} {y:.2}")
+            }
+            OutlineCurve::Quad(_, control, end) => {
+                let ((cx, cy), (x, y)) = (to_px(*control), to_px(*end));
+                write!(path, "Q{cx:.2} {cy:.2} {x:.2} {y:.2}")
+            }
+            OutlineCurve::Cubic(_, control_a, control_b, end) => {
+                let ((ax, ay), (bx, by), (x, y)) =
+                    (to_px(*control_a), to_px(*control_b), to_px(*end));
+                write!(path, "C{ax:.2} {ay:.2} {bx:.2} {by:.2} {x:.2} {y:.2}")
+            }
+        };
+        last = Some(end);
+    }
+
+    (!path.is_empty()).then_some(path)
+}
@@ -0,0 +1,170 @@
+use image::{imageops, Rgb, Rgba, RgbaImage};
+use palette::{LinSrgb, Srgb};
+
+use crate::{
+    chars::font_handler::CharacterBackground, gradient::ColorGradient, grouped_image::PixelGroup,
+};
+
+/// What is painted behind the glyphs of the converted image.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Canvas {
+    /// The color of the [`CharacterBackground`] of the font, so that inverted glyphs
+    /// are drawn on white.
+    #[default]
+    FontBackground,
+    Solid(Rgb<u8>),
+    /// Every cell is filled with its own averaged color multiplied by the factor,
+    /// which should be between `0` and `1`.
+    CellColorDimmed(f64),
+    /// The original image blurred with the given sigma and shown at the given
+    /// opacity, between `0` and `1`, over black.
+    BlurredOriginal {
+        sigma: f32,
+        opacity: f64,
+    },
+}
+
+/// The color the glyphs are drawn with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum GlyphColor {
+    /// Every glyph takes the averaged color of its cell.
+    #[default]
+    Cell,
+    Monochrome(Rgb<u8>),
+    /// Every glyph takes the color of the gradient at the luminance of its cell.
+    Gradient(ColorGradient),
+}
+
+/// How the averaged color of a cell is adjusted to make up for the glyph covering
+/// only part of the cell, which otherwise makes the output darker than the image
+/// on a black background, or lighter on a white one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Whitening {
+    /// The glyph takes the averaged color unchanged.
+    None,
+    /// Adds the same amount to every channel, the less ink the cell needs the more.
+    /// Brightens dark cells well but fades their saturation. On a white background
+    /// the same amount is taken away instead.
+    #[default]
+    Uniform,
+    /// Scales the color in linear light by the ink the glyph is missing, as far as
+    /// the brightest channel allows, which keeps the hue and saturation. On a white
+    /// background the distance to white is scaled instead.
+    Proportional,
+}
+
+impl Whitening {
+    /// Applies the whitening to the linear average color of a group, `ink` being
+    /// the average coverage the glyph is matched against.
+    pub(crate) fn apply(
+        &self,
+        color: LinSrgb<f64>,
+        ink: f64,
+        background: CharacterBackground,
+    ) -> Rgb<u8> {
+        let encoded: Srgb<u8> = Srgb::from_linear(color);
+        let [r, g, b] = [encoded.red, encoded.green, encoded.blue];
+        match (self, background) {
+            (Self::None, _) => Rgb([r, g, b]),
+            (Self::Uniform, CharacterBackground::Black) => {
+                let max = r.max(g).max(b);
+                let add = ((255 - max) as f64 * (1. - ink)) as u8;
+                Rgb([r + add, g + add, b + add])
+            }
+            (Self::Uniform, CharacterBackground::White) => {
+                let min = r.min(g).min(b);
+                let sub = (min as f64 * (1. - ink)) as u8;
+                Rgb([r - sub, g - sub, b - sub])
+            }
+            (Self::Proportional, CharacterBackground::Black) => {
+                let max = color.red.max(color.green).max(color.blue);
+                let factor = (1. / ink.max(f64::EPSILON)).min(1. / max.max(f64::EPSILON));
+                to_rgb(color * factor)
+            }
+            (Self::Proportional, CharacterBackground::White) => {
+                let white = LinSrgb::new(1., 1., 1.);
+                let distance = white - color;
+                let max = distance.red.max(distance.green).max(distance.blue);
+                let factor = (1. / ink.max(f64::EPSILON)).min(1. / max.max(f64::EPSILON));
+                to_rgb(white - distance * factor)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputStyle {
+    pub canvas: Canvas,
+    pub glyph_color: GlyphColor,
+    /// Only applies to [`GlyphColor::Cell`] and to canvases using the cell color.
+    pub whitening: Whitening,
+}
+
+impl Canvas {
+    /// Creates the canvas the glyphs are painted onto, `image` has to be at least as
+    /// big as the canvas.
+    pub(crate) fn image(
+        &self,
+        image: &RgbaImage,
+        width: u32,
+        height: u32,
+        background: CharacterBackground,
+    ) -> RgbaImage {
+        match *self {
+            Self::FontBackground => {
+                let Rgb([r, g, b]) = background.canvas_color();
+                RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]))
+            }
+            Self::Solid(Rgb([r, g, b])) => {
+                RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]))
+            }
+            // filled cell by cell while painting the glyphs
+            Self::CellColorDimmed(_) => RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255])),
+            Self::BlurredOriginal { sigma, opacity } => {
+                let cropped = imageops::crop_imm(image, 0, 0, width, height).to_image();
+                let mut blurred = imageops::blur(&cropped, sigma);
+                blurred.pixels_mut().for_each(|pixel| {
+                    let Rgba([r, g, b, a]) = *pixel;
+                    let Rgb([r, g, b]) = scale(Rgb([r, g, b]), a as f64 / 255. * opacity);
+                    *pixel = Rgba([r, g, b, 255]);
+                });
+                blurred
+            }
+        }
+    }
+
+    /// The color behind the glyph of the group, for the blurred original this is
+    /// only an approximation since the canvas differs from pixel to pixel.
+    pub(crate) fn cell_background(
+        &self,
+        group: &PixelGroup,
+        background: CharacterBackground,
+    ) -> Rgb<u8> {
+        match *self {
+            Self::FontBackground => background.canvas_color(),
+            Self::Solid(color) => color,
+            Self::CellColorDimmed(factor) => scale(group.color, factor),
+            Self::BlurredOriginal { opacity, .. } => scale(group.color, group.alpha * opacity),
+        }
+    }
+}
+
+impl GlyphColor {
+    pub(crate) fn cell_color(&self, group: &PixelGroup) -> Rgb<u8> {
+        match self {
+            Self::Cell => group.color,
+            Self::Monochrome(color) => *color,
+            Self::Gradient(gradient) => gradient.color(group.luminance),
+        }
+    }
+}
+
+fn to_rgb(color: LinSrgb<f64>) -> Rgb<u8> {
+    let color: Srgb<u8> = Srgb::from_linear(color);
+    Rgb([color.red, color.green, color.blue])
+}
+
+/// Multiplies the color by the factor in linear light.
+fn scale(Rgb([r, g, b]): Rgb<u8>, factor: f64) -> Rgb<u8> {
+    to_rgb(Srgb::new(r, g, b).into_linear::<f64>() * factor.clamp(0., 1.))
+}
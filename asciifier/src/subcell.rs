@@ -0,0 +1,344 @@
+use image::{GenericImageView, GrayImage, Luma, Rgb, RgbaImage, SubImage};
+use palette::{LinSrgb, Srgb};
+use serde::{Deserialize, Serialize};
+
+use crate::{render::ansi::BAYER_4X4, Coverage};
+
+/// The first of the 256 Braille patterns, the one without any dots.
+const BRAILLE_BLANK: u32 = 0x2800;
+
+/// The bit of every dot of a Braille pattern, indexed by `[x][y]` of the dot.
+const BRAILLE_DOTS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+/// The first of the sextant characters added with Unicode 13. The block has no
+/// character for the empty, the full, the left and the right half pattern since
+/// those already exist.
+const SEXTANT_START: u32 = 0x1FB00;
+
+/// The quadrant characters indexed by their pattern, in which the top left, top
+/// right, bottom left and bottom right quadrant are the bits from lowest to highest.
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+/// How the cells of the converted image are filled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum CellMode {
+    /// Every cell gets the character of the font that matches it best.
+    #[default]
+    Font,
+    /// Every cell gets the character of the font together with a foreground and a
+    /// background color, chosen jointly to reconstruct the pixels of the cell best.
+    /// Like [`CellMode::Blocks`] the colors are chosen per cell, so the
+    /// [`OutputStyle`](crate::style::OutputStyle) is not applied.
+    TwoColorFont,
+    /// Every cell gets one of the 256 Braille patterns, with each dot of its 2x4
+    /// grid set on its own. Quadruples the resolution, which suits line art and
+    /// plots, but the dots are drawn by the asciifier instead of the font.
+    Braille(DotFill),
+    /// Every cell is split into the regions of a block element, each of which is
+    /// drawn either in the foreground or the background color of the cell. Both
+    /// colors are chosen per cell, so the [`OutputStyle`](crate::style::OutputStyle)
+    /// is not applied, and the text needs a background color to look right, for
+    /// example [`AnsiColoring::ForegroundAndBackground`](crate::render::ansi::AnsiColoring).
+    Blocks(BlockKind),
+}
+
+/// The block elements a cell is split into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockKind {
+    /// `▀` and `▄`, splitting a cell into a top and a bottom half.
+    #[default]
+    HalfBlocks,
+    /// The quadrant characters like `▚`, splitting a cell into 2x2 regions.
+    Quadrants,
+    /// The sextant characters of Unicode 13, splitting a cell into 2x3 regions.
+    /// Not every font has them.
+    Sextants,
+}
+
+impl BlockKind {
+    /// The number of columns and rows of regions in a cell.
+    fn regions(&self) -> (usize, usize) {
+        match self {
+            BlockKind::HalfBlocks => (1, 2),
+            BlockKind::Quadrants => (2, 2),
+            BlockKind::Sextants => (2, 3),
+        }
+    }
+
+    fn num_patterns(&self) -> usize {
+        let (columns, rows) = self.regions();
+        1 << (columns * rows)
+    }
+
+    /// The character of a pattern, whose bits are the regions row by row starting
+    /// at the top left.
+    pub(crate) fn character(&self, pattern: u8) -> char {
+        match self {
+            BlockKind::HalfBlocks => [' ', '▀', '▄', '█'][pattern as usize],
+            BlockKind::Quadrants => QUADRANTS[pattern as usize],
+            BlockKind::Sextants => match pattern {
+                0 => ' ',
+                0b010101 => '▌',
+                0b101010 => '▐',
+                0b111111 => '█',
+                _ => {
+                    let skipped = (pattern > 0b010101) as u32 + (pattern > 0b101010) as u32;
+                    // the sextants are ordered by pattern with the ones above removed
+                    char::from_u32(SEXTANT_START + pattern as u32 - 1 - skipped).unwrap()
+                }
+            },
+        }
+    }
+}
+
+impl CellMode {
+    /// The masks of every pattern of the mode indexed by the pattern, in which
+    /// `255` means full ink. Empty for the font modes, whose glyphs come rasterized
+    /// with the font.
+    pub(crate) fn masks(&self, char_box: (usize, usize)) -> Vec<GrayImage> {
+        match self {
+            CellMode::Font | CellMode::TwoColorFont => vec![],
+            CellMode::Braille(_) => (0..=u8::MAX)
+                .map(|pattern| braille_mask(pattern, char_box))
+                .collect(),
+            CellMode::Blocks(kind) => (0..kind.num_patterns())
+                .map(|pattern| block_mask(*kind, pattern as u8, char_box))
+                .collect(),
+        }
+    }
+}
+
+/// Decides which dots of a Braille pattern are set.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DotFill {
+    /// A dot is set if the ink of its part of the cell reaches the threshold, which
+    /// should be between `0` and `1`.
+    Threshold(f64),
+    /// The threshold of every dot is taken from a 4x4 Bayer matrix laid across the
+    /// whole image, which keeps smooth gradients visible.
+    OrderedDither,
+}
+
+impl Default for DotFill {
+    fn default() -> Self {
+        Self::Threshold(0.5)
+    }
+}
+
+/// Picks the Braille pattern for a cell and returns it together with the distance
+/// between the cells coverage and the coverage of the pattern.
+///
+/// * `cell` - position of the cell in cells, used to place the dots in the dither
+pub(crate) fn braille_pattern(
+    coverage: &Coverage,
+    fill: DotFill,
+    (cell_x, cell_y): (usize, usize),
+) -> (u8, f64) {
+    // the coverage is sampled on a 4x4 grid stored column by column, so every dot
+    // covers two neighbouring squares of a row
+    let squares = coverage.squares();
+    let mut pattern = 0;
+    let mut pattern_squares = [0.; 16];
+    for (dot_x, dots) in BRAILLE_DOTS.iter().enumerate() {
+        for (dot_y, dot) in dots.iter().enumerate() {
+            let (left, right) = (dot_x * 2 * 4 + dot_y, (dot_x * 2 + 1) * 4 + dot_y);
+            let ink = (squares[left] + squares[right]) / 2.;
+            let threshold = match fill {
+                DotFill::Threshold(threshold) => threshold,
+                DotFill::OrderedDither => {
+                    let (x, y) = (cell_x * 2 + dot_x, cell_y * 4 + dot_y);
+                    (BAYER_4X4[y % 4][x % 4] as f64 + 0.5) / 16.
+                }
+            };
+            if ink >= threshold {
+                pattern |= dot;
+                pattern_squares[left] = 1.;
+                pattern_squares[right] = 1.;
+            }
+        }
+    }
+    (
+        pattern,
+        coverage.dist(&Coverage::from_squares(pattern_squares)),
+    )
+}
+
+pub(crate) fn braille_char(pattern: u8) -> char {
+    // every value of a u8 added to the blank pattern is a Braille pattern
+    char::from_u32(BRAILLE_BLANK + pattern as u32).unwrap()
+}
+
+/// Draws the dots of the pattern as anti-aliased disks into a mask of the size of
+/// a cell.
+fn braille_mask(pattern: u8, (width, height): (usize, usize)) -> GrayImage {
+    let (dot_width, dot_height) = (width as f32 / 2., height as f32 / 4.);
+    let radius = dot_width.min(dot_height) * 0.35;
+    GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        let (dot_x, dot_y) = (
+            ((x as f32 / dot_width) as usize).min(1),
+            ((y as f32 / dot_height) as usize).min(3),
+        );
+        if pattern & BRAILLE_DOTS[dot_x][dot_y] == 0 {
+            return Luma([0]);
+        }
+        let center = (
+            (dot_x as f32 + 0.5) * dot_width,
+            (dot_y as f32 + 0.5) * dot_height,
+        );
+        let distance = (x as f32 + 0.5 - center.0).hypot(y as f32 + 0.5 - center.1);
+        Luma([((radius - distance + 0.5).clamp(0., 1.) * 255.) as u8])
+    })
+}
+
+/// The block pattern and colors chosen for a cell.
+pub(crate) struct BlockMatch {
+    pub pattern: u8,
+    pub foreground: Rgb<u8>,
+    pub background: Rgb<u8>,
+    /// The root mean square difference between the pixels and the color of their
+    /// region, between `0` and `1`.
+    pub distance: f64,
+}
+
+/// The alpha weighted sums of the pixels in one region of a cell, in linear light.
+#[derive(Debug, Clone, Copy, Default)]
+struct RegionSums {
+    weight: f64,
+    sum: [f64; 3],
+    squared_sum: f64,
+}
+
+impl RegionSums {
+    fn add(self, other: Self) -> Self {
+        Self {
+            weight: self.weight + other.weight,
+            sum: [0, 1, 2].map(|i| self.sum[i] + other.sum[i]),
+            squared_sum: self.squared_sum + other.squared_sum,
+        }
+    }
+
+    fn mean(&self) -> Option<Rgb<u8>> {
+        (self.weight > 0.).then(|| {
+            let [r, g, b] = self.sum.map(|sum| sum / self.weight);
+            let mean: Srgb<u8> = Srgb::from_linear(LinSrgb::new(r, g, b));
+            Rgb([mean.red, mean.green, mean.blue])
+        })
+    }
+
+    /// The sum of the squared differences between the pixels and their mean.
+    fn squared_error(&self) -> f64 {
+        if self.weight == 0. {
+            return 0.;
+        }
+        let squared_sum_of_mean = self.sum.iter().map(|sum| sum * sum).sum::<f64>() / self.weight;
+        self.squared_sum - squared_sum_of_mean
+    }
+}
+
+/// Tries every pattern of the block kind with the mean colors of the regions on
+/// either side and keeps the one reconstructing the pixels of the cell best.
+pub(crate) fn block_match(image: SubImage<&RgbaImage>, kind: BlockKind) -> BlockMatch {
+    let (columns, rows) = kind.regions();
+    let (width, height) = image.dimensions();
+    let mut regions = vec![RegionSums::default(); columns * rows];
+    for (x, y, pixel) in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        let LinSrgb {
+            red: r,
+            green: g,
+            blue: b,
+            ..
+        } = Srgb::new(r, g, b).into_linear::<f64>();
+        let weight = a as f64 / 255.;
+        let region = &mut regions[region_index(x, y, (width, height), (columns, rows))];
+        region.weight += weight;
+        region.sum = [0, 1, 2].map(|i| region.sum[i] + [r, g, b][i] * weight);
+        region.squared_sum += (r * r + g * g + b * b) * weight;
+    }
+
+    // going from the full pattern down makes flat cells a full block, which keeps
+    // their color when only the foreground is shown
+    let (pattern, foreground, background, error) = (0..kind.num_patterns())
+        .rev()
+        .map(|pattern| {
+            let (ink, canvas) = regions.iter().enumerate().fold(
+                (RegionSums::default(), RegionSums::default()),
+                |(ink, canvas), (i, region)| {
+                    if pattern & (1 << i) != 0 {
+                        (ink.add(*region), canvas)
+                    } else {
+                        (ink, canvas.add(*region))
+                    }
+                },
+            );
+            (
+                pattern,
+                ink,
+                canvas,
+                ink.squared_error() + canvas.squared_error(),
+            )
+        })
+        // the tolerance keeps rounding errors from splitting flat cells
+        .reduce(|best, current| {
+            if current.3 < best.3 - 1e-6 {
+                current
+            } else {
+                best
+            }
+        })
+        .unwrap();
+
+    // a side without any regions takes the color of the other one
+    let foreground_color = foreground
+        .mean()
+        .or_else(|| background.mean())
+        .unwrap_or(Rgb([0, 0, 0]));
+    let background_color = background.mean().unwrap_or(foreground_color);
+    let weight = foreground.weight + background.weight;
+    let distance = if weight > 0. {
+        (error.max(0.) / (weight * 3.)).sqrt()
+    } else {
+        0.
+    };
+
+    BlockMatch {
+        pattern: pattern as u8,
+        foreground: foreground_color,
+        background: background_color,
+        distance,
+    }
+}
+
+fn region_index(
+    x: u32,
+    y: u32,
+    (width, height): (u32, u32),
+    (columns, rows): (usize, usize),
+) -> usize {
+    let column = x as usize * columns / width as usize;
+    let row = y as usize * rows / height as usize;
+    row * columns + column
+}
+
+fn block_mask(kind: BlockKind, pattern: u8, (width, height): (usize, usize)) -> GrayImage {
+    let regions = kind.regions();
+    GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        let region = region_index(x, y, (width as u32, height as u32), regions);
+        Luma([if pattern & (1 << region) != 0 { 255 } else { 0 }])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sextants_skip_the_half_blocks() {
+        let sextant = |pattern| BlockKind::Sextants.character(pattern);
+        assert_eq!(sextant(1), '\u{1FB00}');
+        assert_eq!(sextant(22), '\u{1FB14}');
+        assert_eq!(sextant(62), '\u{1FB3B}');
+    }
+}
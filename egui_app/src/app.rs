@@ -9,7 +9,7 @@ use egui::{
     Align, CentralPanel, Context, Frame, Image, Label, Layout, RichText, ScrollArea, SidePanel,
     Slider, Stroke, TextureHandle, TextureOptions, Ui, Vec2, Vec2b, Widget, Window,
 };
-use image::{DynamicImage, EncodableLayout, ImageBuffer, Rgb};
+use image::{DynamicImage, EncodableLayout};
 
 use crate::{file_selection::FileSelection, font_builder::FontBuilderControls};
 
@@ -53,7 +53,7 @@ impl App for AsciifierApp {
                     if let Some(file) = self.file_selection.selected_image() {
                         match self.asciify_image(&file).cloned() {
                             Ok(image) => {
-                                self.set_image(image.into(), ui);
+                                self.set_image(image, ui);
                             }
                             Err(error) => {
                                 self.showing_error = Some(format!("{error}"));
@@ -150,10 +150,7 @@ impl AsciifierApp {
         }
     }
 
-    fn asciify_image(
-        &mut self,
-        file: &PathBuf,
-    ) -> Result<&ImageBuffer<Rgb<u8>, Vec<u8>>, AsciiError> {
+    fn asciify_image(&mut self, file: &PathBuf) -> Result<&DynamicImage, AsciiError> {
         let font_builder = self.font_controls.font_builder();
         let mut builder = Asciifier::load_image(file)?.font(|mut builder| {
             builder.copy(&font_builder);
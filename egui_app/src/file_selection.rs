@@ -21,8 +21,8 @@ impl FileSelection {
             .peekable();
         let (uuid, file) = new_files.peek().unwrap();
 
-        if self.files.is_empty() && file.path.is_some() {
-            self.selected_image = Some((*uuid, file.path.as_ref().unwrap().clone()));
+        if let (true, Some(path)) = (self.files.is_empty(), &file.path) {
+            self.selected_image = Some((*uuid, path.clone()));
         }
         self.files.extend(new_files);
     }